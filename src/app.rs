@@ -1,19 +1,42 @@
-use crate::commands::{ActionCommand, ControlCommand, NavigationCommand};
-use crate::models::RomLibrary;
-use crate::ui::renderer::Renderer;
-use crate::ui::widgets::common::Widget;
-use crate::ui::widgets::panel::SplitPanelWidget;
-use crate::ui::widgets::{self, CarouselWidget, GameWidget, ListWidget};
+use crate::commands::{ActionCommand, ControlCommand, NavigationCommand, UiEvent};
+use crate::config::{Config, MameConfig};
+#[cfg(unix)]
+use crate::control::{ControlServer, Request};
+use crate::metadata::{GameMetadata, MetadataOverrides};
+use crate::models::{MediaPaths, RomLibrary, SharedLibrary};
+use crate::replay::{Input, Recorder, Replay};
+use crate::state::UserState;
+use crate::storage;
+use crate::ui::images::{ImageCache, SharedImages};
+use crate::ui::layout::Layout;
+use crate::ui::renderer::{self, Renderer};
+use crate::ui::tui::Rotation;
+use crate::ui::widgets::common::{Modal, Widget};
+use crate::ui::widgets::overlay::OverlayWidget;
+use crate::ui::widgets::{AttractWidget, FilterPanelWidget, SearchWidget};
+use crate::web::{self, Response, WebServer};
+use serde_json::{Value, json};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::path::PathBuf;
 use std::rc::Rc;
-use std::time::{Duration, Instant};
+use std::sync::mpsc::{Receiver, TryRecvError, channel};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tiny_skia::Pixmap;
 use winit::application::ApplicationHandler;
 use winit::event::{ElementState, KeyEvent, WindowEvent};
-use winit::event_loop::ActiveEventLoop;
+use winit::event_loop::{ActiveEventLoop, ControlFlow};
 use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::{Window, WindowId};
 
 const REPEAT_DELAY: Duration = Duration::from_millis(400);
 const REPEAT_INTERVAL: Duration = Duration::from_millis(80);
+/// How often finished background decodes are picked up while any are queued.
+const IMAGE_POLL_INTERVAL: Duration = Duration::from_millis(30);
+/// How long a screenshot waits for the images on screen to be decoded.
+const SCREENSHOT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often a running rescan is checked for completion.
+const RESCAN_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 struct CommandState {
     last_trigger: Instant,
@@ -21,14 +44,35 @@ struct CommandState {
     repeating: bool,
 }
 
-type MainSplit = SplitPanelWidget<ListWidget, GameWidget>;
-type RootLayout = SplitPanelWidget<CarouselWidget, MainSplit>;
+type FilteredLayout = OverlayWidget<Box<dyn Widget>, FilterPanelWidget>;
+type SearchLayout = OverlayWidget<FilteredLayout, SearchWidget>;
+type RootLayout = OverlayWidget<SearchLayout, AttractWidget>;
 
 pub struct OsirisApp {
     pub window: Option<Rc<Window>>,
     pub renderer: Renderer,
     root_panel: RootLayout,
     active_command: (NavigationCommand, CommandState),
+    library: SharedLibrary,
+    images: SharedImages,
+    last_input: Instant,
+    attract_after: Option<Duration>,
+    idle: bool,
+    control_rotation: Rotation,
+    state_file: PathBuf,
+    roms_dir: PathBuf,
+    mame: MameConfig,
+    metadata: MetadataOverrides,
+    metadata_file: PathBuf,
+    /// The library being scanned again in the background.
+    rescan: Option<Receiver<RomLibrary>>,
+    recorder: Option<Recorder>,
+    replay: Option<Replay>,
+    /// Selected system and game, as announced by the widgets.
+    selection: (usize, Option<usize>),
+    #[cfg(unix)]
+    control: Option<ControlServer>,
+    web: Option<WebServer>,
 }
 
 impl ApplicationHandler for OsirisApp {
@@ -43,6 +87,7 @@ impl ApplicationHandler for OsirisApp {
                     )
                     .unwrap(),
             );
+            window.request_redraw();
             self.window = Some(window);
         }
     }
@@ -60,8 +105,12 @@ impl ApplicationHandler for OsirisApp {
                     },
                 ..
             } => {
+                // Pressing anything takes over from a replay
+                if state == ElementState::Pressed && self.replay.take().is_some() {
+                    println!("OSIRIS: REPLAY INTERRUPTED.");
+                }
                 let command = self.map_key(key, state);
-                self.handle_control_command(command);
+                self.handle_input(command.map_or(Input::Release, Input::Press));
             }
             WindowEvent::RedrawRequested => {
                 if let Some(window) = &self.window {
@@ -72,39 +121,122 @@ impl ApplicationHandler for OsirisApp {
         }
     }
 
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
         let now = Instant::now();
+        #[cfg(unix)]
+        self.answer_control_requests();
+        self.answer_web_requests();
+        self.pick_up_rescan();
+
+        let mut replayed = Vec::new();
+        if let Some(replay) = &mut self.replay {
+            while let Some(input) = replay.next_due(now) {
+                replayed.push(input);
+            }
+        }
+        for input in replayed {
+            self.handle_input(input);
+        }
+        if self.replay.as_ref().is_some_and(Replay::is_finished) {
+            self.replay = None;
+            println!("OSIRIS: REPLAY FINISHED.");
+        }
+
+        // A replay brings its own auto-repeats
         let (cmd, state) = &mut self.active_command;
+        let holding = *cmd != NavigationCommand::None && self.replay.is_none();
 
         if !state.repeating && now.duration_since(state.started_at) >= REPEAT_DELAY {
             state.repeating = true;
         }
 
-        if state.repeating && now.duration_since(state.last_trigger) >= REPEAT_INTERVAL {
-            state.last_trigger = now;
-            let event = self
-                .root_panel
-                .handle_command(ControlCommand::Navigation(cmd.clone()));
-            self.root_panel.handle_ui_event(event);
+        if holding && state.repeating && now.duration_since(state.last_trigger) >= REPEAT_INTERVAL {
+            let cmd = ControlCommand::Navigation(cmd.clone());
+            self.handle_input(Input::Repeat(cmd));
         }
 
-        if let Some(window) = &self.window {
+        let attract_at = self
+            .attract_after
+            .filter(|_| !self.idle)
+            .map(|after| self.last_input + after);
+        if attract_at.is_some_and(|at| now >= at) {
+            self.dispatch_ui_event(UiEvent::Idle);
+            // With nothing to show (e.g. an empty library) try again after
+            // another timeout
+            self.idle = self.root_panel.modal().is_active();
+            if !self.idle {
+                self.last_input = now;
+            }
+        }
+
+        // Pick up images decoded in the background
+        if self.images.borrow_mut().poll() {
+            self.dispatch_ui_event(UiEvent::ImagesLoaded);
+        }
+
+        // Widgets flag themselves dirty on input, events and animation frames
+        let next_frame = self.root_panel.next_frame(now);
+        if self.root_panel.is_dirty()
+            && let Some(window) = &self.window
+        {
             window.request_redraw();
         }
+
+        // Sleep until the next key repeat, replayed input, animation frame,
+        // decode or rescan check
+        let (_, state) = &self.active_command;
+        let repeat_at = holding.then(|| {
+            if state.repeating {
+                state.last_trigger + REPEAT_INTERVAL
+            } else {
+                state.started_at + REPEAT_DELAY
+            }
+        });
+        let poll_at = self
+            .images
+            .borrow()
+            .is_loading()
+            .then(|| now + IMAGE_POLL_INTERVAL);
+        let rescan_at = self.rescan.is_some().then(|| now + RESCAN_POLL_INTERVAL);
+
+        let attract_at = self
+            .attract_after
+            .filter(|_| !self.idle)
+            .map(|after| self.last_input + after);
+        let replay_at = self.replay.as_ref().and_then(Replay::next_at);
+        let wake_at = [
+            repeat_at, replay_at, poll_at, rescan_at, next_frame, attract_at,
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        event_loop.set_control_flow(wake_at.map_or(ControlFlow::Wait, ControlFlow::WaitUntil));
     }
 }
 
 impl OsirisApp {
-    pub fn new(renderer: Renderer, library: RomLibrary) -> Self {
-        let library = Rc::new(library);
-        let carousel = CarouselWidget::new(library.clone());
-        let game_list = widgets::ListWidget::new(library.clone());
-        let metadata = GameWidget::new(library.clone());
-
-        let main_split = SplitPanelWidget::new(game_list, metadata, 35, true, false);
-        let root_panel = SplitPanelWidget::new(carousel, main_split, 20, true, true);
+    pub fn new(renderer: Renderer, mut library: RomLibrary, config: &Config) -> Self {
+        UserState::load(&config.state_file).apply(&mut library);
+        let metadata = MetadataOverrides::load(&config.metadata_file);
+        metadata.apply(&mut library);
+        let library = Rc::new(RefCell::new(library));
+        let images = Rc::new(RefCell::new(ImageCache::new(config.image_cache_size)));
+        let main_layout = Layout::load(&config.layout_file).build(&library, &images, config);
+        let filtered = OverlayWidget::new(main_layout, FilterPanelWidget::new(library.clone()));
+        let searchable = OverlayWidget::new(
+            filtered,
+            SearchWidget::new(library.clone(), config.cabinet.clone()),
+        );
+        let attract = AttractWidget::new(
+            library.clone(),
+            images.clone(),
+            Duration::from_secs(config.attract_slide_seconds.max(1)),
+            config.attract_dim,
+            config.cabinet.clone(),
+        );
+        let root_panel = OverlayWidget::new(searchable, attract);
 
-        Self {
+        let mut app = Self {
             window: None,
             renderer,
             root_panel,
@@ -116,12 +248,432 @@ impl OsirisApp {
                     repeating: false,
                 },
             ),
+            library,
+            images,
+            last_input: Instant::now(),
+            attract_after: (config.attract_minutes > 0)
+                .then(|| Duration::from_secs(config.attract_minutes * 60)),
+            idle: false,
+            control_rotation: if config.rotate_controls {
+                config.rotation
+            } else {
+                Rotation::None
+            },
+            state_file: config.state_file.clone(),
+            roms_dir: config.roms_dir.clone(),
+            mame: config.mame.clone(),
+            metadata,
+            metadata_file: config.metadata_file.clone(),
+            rescan: None,
+            recorder: None,
+            replay: None,
+            selection: (0, None),
+            #[cfg(unix)]
+            control: None,
+            web: None,
+        };
+        // Let every widget pick up the initial selection
+        if !app.library.borrow().systems.is_empty() {
+            app.dispatch_ui_event(UiEvent::SystemChanged(0));
+        }
+        app
+    }
+
+    /// Writes every input of the session to `recorder`.
+    pub fn record(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Plays `replay` back in place of the controls, until it ends or a key
+    /// is pressed.
+    pub fn replay(&mut self, replay: Replay) {
+        self.replay = Some(replay);
+    }
+
+    /// Takes requests from control clients and tells subscribers about
+    /// selection changes and launches.
+    #[cfg(unix)]
+    pub fn serve_control(&mut self, server: ControlServer) {
+        self.control = Some(server);
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn answer_control_requests(&mut self) {
+        while let Some((request, client)) = self.control.as_mut().and_then(ControlServer::poll) {
+            let reply = match request {
+                Request::Status => {
+                    let mut status = self.describe(self.selection.0, self.selection.1);
+                    status["ok"] = json!(true);
+                    status
+                }
+                Request::Command { command } => match command.parse::<ControlCommand>() {
+                    Ok(cmd) => {
+                        // Leaves whatever the player holds repeating
+                        self.handle_input(Input::Tap(cmd));
+                        json!({ "ok": true })
+                    }
+                    Err(e) => json!({ "ok": false, "error": e }),
+                },
+                Request::Launch { system, game } => {
+                    let system = system.unwrap_or(self.selection.0);
+                    let game = game.or(self.selection.1);
+                    let exists = game.is_some_and(|game| {
+                        let library = self.library.borrow();
+                        library
+                            .systems
+                            .get(system)
+                            .is_some_and(|s| game < s.games.len())
+                    });
+                    match game {
+                        Some(game) if exists => {
+                            self.dispatch_ui_event(UiEvent::LaunchGame(system, game));
+                            json!({ "ok": true })
+                        }
+                        _ => json!({ "ok": false, "error": "no such game" }),
+                    }
+                }
+                Request::Subscribe => json!({ "ok": true }),
+            };
+            client.reply(reply);
+        }
+    }
+
+    /// Takes requests from the web management page.
+    pub fn serve_web(&mut self, server: WebServer) {
+        self.web = Some(server);
+    }
+
+    pub(crate) fn answer_web_requests(&mut self) {
+        while let Some((request, responder)) = self.web.as_ref().and_then(WebServer::poll) {
+            responder.respond(self.answer_web_request(request));
+        }
+    }
+
+    fn answer_web_request(&mut self, request: web::Request) -> Response {
+        let (system_name, game_id) = match &request {
+            web::Request::Systems => {
+                let library = self.library.borrow();
+                let systems: Vec<_> = library
+                    .systems
+                    .iter()
+                    .map(|s| json!({ "name": s.name, "games": s.games.len() }))
+                    .collect();
+                return Response::json(
+                    200,
+                    json!({ "systems": systems, "rescanning": self.rescan.is_some() }),
+                );
+            }
+            web::Request::Games { system } => {
+                let library = self.library.borrow();
+                return match library.systems.iter().find(|s| &s.name == system) {
+                    Some(s) => Response::json(200, json!(s.games)),
+                    None => Response::error(404, format!("no system {:?}", system)),
+                };
+            }
+            web::Request::Rescan => {
+                if self.rescan.is_none() {
+                    println!("OSIRIS: RESCANNING {}...", self.roms_dir.display());
+                    let (tx, rx) = channel();
+                    let (roms_dir, mame) = (self.roms_dir.clone(), self.mame.clone());
+                    std::thread::spawn(move || tx.send(storage::load_library(roms_dir, &mame)));
+                    self.rescan = Some(rx);
+                }
+                return Response::json(202, json!({ "rescanning": true }));
+            }
+            web::Request::EditGame { system, game, .. }
+            | web::Request::ToggleFavorite { system, game }
+            | web::Request::Media { system, game, .. }
+            | web::Request::UploadMedia { system, game, .. } => (system.clone(), game.clone()),
+        };
+
+        let found = self.library.borrow().find_game(&system_name, &game_id);
+        let Some((system_idx, game_idx)) = found else {
+            return Response::error(404, format!("no game {:?} in {:?}", game_id, system_name));
+        };
+
+        match request {
+            web::Request::EditGame { edit, .. } => {
+                self.edit_game(system_idx, game_idx, *edit);
+                let library = self.library.borrow();
+                Response::json(200, json!(library.systems[system_idx].games[game_idx]))
+            }
+            web::Request::ToggleFavorite { .. } => {
+                let favorite = {
+                    let mut library = self.library.borrow_mut();
+                    let game = &mut library.systems[system_idx].games[game_idx];
+                    game.favorite = !game.favorite;
+                    game.favorite
+                };
+                self.dispatch_ui_event(UiEvent::UserStateChanged);
+                Response::json(200, json!({ "favorite": favorite }))
+            }
+            web::Request::Media { kind, .. } => {
+                let library = self.library.borrow();
+                let game = &library.systems[system_idx].games[game_idx];
+                let Some(path) = game.media.get(kind) else {
+                    return Response::error(404, format!("{} has no {:?}", game_id, kind));
+                };
+                // Only files of the library are served, whatever the metadata
+                // file says
+                let inside_roms = path
+                    .canonicalize()
+                    .ok()
+                    .zip(self.roms_dir.canonicalize().ok())
+                    .is_some_and(|(path, roms)| path.starts_with(roms));
+                if !inside_roms {
+                    return Response::error(
+                        403,
+                        format!(
+                            "the {:?} of {} is outside the ROMs directory",
+                            kind, game_id
+                        ),
+                    );
+                }
+                let content_type = image::ImageFormat::from_path(path)
+                    .map_or("application/octet-stream", |f| f.to_mime_type());
+                match std::fs::read(path) {
+                    Ok(data) => Response::file(content_type, data),
+                    Err(e) => Response::error(404, format!("{}: {}", path.display(), e)),
+                }
+            }
+            web::Request::UploadMedia { kind, data, .. } => {
+                let system_dir = {
+                    let library = self.library.borrow();
+                    let game = &library.systems[system_idx].games[game_idx];
+                    game.path.parent().map(PathBuf::from).unwrap_or_default()
+                };
+                let path = match storage::save_media(&system_dir, &game_id, kind, &data) {
+                    Ok(path) => path,
+                    Err(e) => return Response::error(415, e),
+                };
+                // A file of the same name may be cached from before
+                self.images.borrow_mut().forget(&path);
+                let mut media = MediaPaths::default();
+                media.set(kind, path.clone());
+                self.edit_game(
+                    system_idx,
+                    game_idx,
+                    GameMetadata {
+                        media,
+                        ..GameMetadata::default()
+                    },
+                );
+                Response::json(200, json!({ "path": path }))
+            }
+            web::Request::Systems | web::Request::Games { .. } | web::Request::Rescan => {
+                unreachable!("answered above")
+            }
         }
     }
 
-    fn handle_control_command(&mut self, command: Option<ControlCommand>) {
+    /// Applies `edit` to a game, keeps it in the metadata file and shows it.
+    fn edit_game(&mut self, system_idx: usize, game_idx: usize, edit: GameMetadata) {
+        {
+            let mut library = self.library.borrow_mut();
+            let system = &mut library.systems[system_idx];
+            let game = &mut system.games[game_idx];
+            edit.apply(game);
+            self.metadata.update(&system.name, &game.id, &edit);
+        }
+        self.metadata.save(&self.metadata_file);
+        self.dispatch_ui_event(UiEvent::LibraryChanged);
+    }
+
+    /// Swaps in the library of a finished rescan, with the user state and
+    /// metadata edits carried over and the selected game kept if it is still
+    /// there.
+    fn pick_up_rescan(&mut self) {
+        let Some(rescan) = &self.rescan else {
+            return;
+        };
+        let mut library = match rescan.try_recv() {
+            Ok(library) => library,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                println!("⚠️ OSIRIS: RESCAN FAILED. KEEPING THE CURRENT LIBRARY.");
+                self.rescan = None;
+                return;
+            }
+        };
+        self.rescan = None;
+        println!(
+            "OSIRIS: RESCAN COMPLETE. SYSTEMS DETECTED: {}",
+            library.systems.len()
+        );
+
+        let (system_name, game_id) = {
+            let old = self.library.borrow();
+            UserState::capture(&old).apply(&mut library);
+            let system = old.systems.get(self.selection.0);
+            let game = system
+                .zip(self.selection.1)
+                .and_then(|(s, g)| s.games.get(g));
+            (
+                system.map(|s| s.name.clone()).unwrap_or_default(),
+                game.map(|g| g.id.clone()),
+            )
+        };
+        self.metadata.apply(&mut library);
+
+        let found = game_id.and_then(|id| library.find_game(&system_name, &id));
+        let system_idx = found.map(|(system_idx, _)| system_idx).unwrap_or_else(|| {
+            library
+                .systems
+                .iter()
+                .position(|s| s.name == system_name)
+                .unwrap_or(0)
+        });
+        *self.library.borrow_mut() = library;
+        self.dispatch_ui_event(UiEvent::LibraryReplaced(system_idx));
+        if let Some((system_idx, game_idx)) = found {
+            self.dispatch_ui_event(UiEvent::JumpToGame(system_idx, game_idx));
+        }
+        // Attract mode closes itself when nothing is left to show
+        self.idle &= self.root_panel.modal().is_active();
+    }
+
+    /// `{"system": {"index", "name"}, "game": {"index", "id", "name"}}`,
+    /// with nulls for what does not exist.
+    fn describe(&self, system_idx: usize, game_idx: Option<usize>) -> Value {
+        let library = self.library.borrow();
+        let system = library.systems.get(system_idx);
+        let game = system.zip(game_idx).and_then(|(s, g)| s.games.get(g));
+        json!({
+            "system": system.map(|s| json!({ "index": system_idx, "name": s.name })),
+            "game": game.map(|g| json!({ "index": game_idx, "id": g.id, "name": g.name })),
+        })
+    }
+
+    /// Applies what is left of the replay at once.
+    fn finish_replay(&mut self) {
+        if let Some(mut replay) = self.replay.take() {
+            for input in replay.drain() {
+                self.handle_input(input);
+            }
+        }
+    }
+
+    /// Renders the screen a replay (if any) leads to without a window, once
+    /// the images it shows are decoded.
+    pub fn screenshot(&mut self, width: u32, height: u32) -> Option<Pixmap> {
+        // Lists scroll by their height, so lay out as the first frame would
+        renderer::lay_out(&self.renderer.tui, &mut self.root_panel, width, height);
+        self.finish_replay();
+
+        let deadline = Instant::now() + SCREENSHOT_TIMEOUT;
+        loop {
+            if self.images.borrow_mut().poll() {
+                self.dispatch_ui_event(UiEvent::ImagesLoaded);
+            }
+            if !self.images.borrow().is_loading() || Instant::now() >= deadline {
+                break;
+            }
+            std::thread::sleep(IMAGE_POLL_INTERVAL);
+        }
+        renderer::render_offscreen(&self.renderer.tui, &mut self.root_panel, width, height)
+    }
+
+    fn dispatch_ui_event(&mut self, event: UiEvent) {
+        let mut queue = VecDeque::from([event]);
+        while let Some(event) = queue.pop_front() {
+            let notification = match event {
+                UiEvent::None => continue,
+                UiEvent::Batch(events) => {
+                    queue.extend(events);
+                    continue;
+                }
+                UiEvent::SystemChanged(system_idx)
+                | UiEvent::JumpToGame(system_idx, _)
+                | UiEvent::LibraryReplaced(system_idx) => {
+                    // The list follows up with the GameChanged of its selection
+                    self.selection = (system_idx, None);
+                    Some(("system_changed", self.describe(system_idx, None)))
+                }
+                UiEvent::GameChanged(game_idx) => {
+                    self.selection.1 = game_idx;
+                    Some(("game_changed", self.describe(self.selection.0, game_idx)))
+                }
+                UiEvent::LaunchGame(system_idx, game_idx) => {
+                    self.record_launch(system_idx, game_idx);
+                    Some(("launch_game", self.describe(system_idx, Some(game_idx))))
+                }
+                UiEvent::UserStateChanged => {
+                    self.save_state();
+                    None
+                }
+                _ => None,
+            };
+            #[cfg(unix)]
+            if let (Some(control), Some((name, mut details))) = (&mut self.control, notification) {
+                details["event"] = json!(name);
+                control.notify(details);
+            }
+            #[cfg(not(unix))]
+            let _ = notification;
+
+            queue.push_back(self.root_panel.handle_ui_event(event));
+        }
+    }
+
+    fn record_launch(&mut self, system_idx: usize, game_idx: usize) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        if let Some(game) = self
+            .library
+            .borrow_mut()
+            .systems
+            .get_mut(system_idx)
+            .and_then(|s| s.games.get_mut(game_idx))
+        {
+            game.play_count += 1;
+            game.last_played = Some(now);
+        }
+        self.save_state();
+    }
+
+    fn save_state(&self) {
+        UserState::capture(&self.library.borrow()).save(&self.state_file);
+    }
+
+    fn handle_input(&mut self, input: Input) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(&input);
+        }
+
+        match input {
+            Input::Press(cmd) => self.handle_control_command(Some(cmd), true),
+            Input::Tap(cmd) => self.handle_control_command(Some(cmd), false),
+            Input::Release => self.handle_control_command(None, true),
+            Input::Repeat(cmd) => {
+                let now = Instant::now();
+                self.active_command.1.last_trigger = now;
+                self.last_input = now;
+                let event = self.root_panel.handle_command(cmd);
+                self.dispatch_ui_event(event);
+            }
+        }
+    }
+
+    /// Presses `command`, or releases all controls. Only a held press takes
+    /// over the auto-repeat.
+    fn handle_control_command(&mut self, command: Option<ControlCommand>, hold: bool) {
         if let Some(cmd) = command {
+            self.last_input = Instant::now();
+            // Attract mode may also have closed itself, e.g. after a rescan
+            if std::mem::take(&mut self.idle) && self.root_panel.modal().is_active() {
+                // The wake-up press only closes attract mode, it must not
+                // start an auto-repeat on the list underneath either
+                if hold {
+                    self.active_command.0 = NavigationCommand::None;
+                }
+                self.root_panel.handle_command(cmd);
+                return;
+            }
+
             match cmd {
+                _ if !hold => {}
                 ControlCommand::Navigation(ref nav_cmd) => {
                     self.active_command = (
                         nav_cmd.clone(),
@@ -138,29 +690,121 @@ impl OsirisApp {
             }
 
             let event = self.root_panel.handle_command(cmd);
-            self.root_panel.handle_ui_event(event);
-
-            if let Some(window) = &self.window {
-                window.request_redraw();
-            }
+            self.dispatch_ui_event(event);
         } else {
             self.active_command.0 = NavigationCommand::None;
         }
     }
 
+    fn navigation(&self, nav: NavigationCommand) -> ControlCommand {
+        ControlCommand::Navigation(self.control_rotation.remap(nav))
+    }
+
     fn map_key(&self, key: KeyCode, state: ElementState) -> Option<ControlCommand> {
         if state == ElementState::Released {
             return None;
         }
 
         match key {
-            KeyCode::ArrowUp => Some(ControlCommand::Navigation(NavigationCommand::Up)),
-            KeyCode::ArrowDown => Some(ControlCommand::Navigation(NavigationCommand::Down)),
-            KeyCode::ArrowLeft => Some(ControlCommand::Navigation(NavigationCommand::Left)),
-            KeyCode::ArrowRight => Some(ControlCommand::Navigation(NavigationCommand::Right)),
+            KeyCode::ArrowUp => Some(self.navigation(NavigationCommand::Up)),
+            KeyCode::ArrowDown => Some(self.navigation(NavigationCommand::Down)),
+            KeyCode::ArrowLeft => Some(self.navigation(NavigationCommand::Left)),
+            KeyCode::ArrowRight => Some(self.navigation(NavigationCommand::Right)),
             KeyCode::Space => Some(ControlCommand::Action(ActionCommand::Select)),
             KeyCode::Escape => Some(ControlCommand::Action(ActionCommand::Back)),
+            KeyCode::KeyR => Some(ControlCommand::Action(ActionCommand::ToggleOneGameOneRom)),
+            KeyCode::Tab => Some(ControlCommand::Action(ActionCommand::NextAlternate)),
+            KeyCode::KeyS => Some(ControlCommand::Action(ActionCommand::CycleSort)),
+            KeyCode::KeyF => Some(ControlCommand::Action(ActionCommand::OpenFilters)),
+            KeyCode::KeyM => Some(ControlCommand::Action(ActionCommand::ToggleFavorite)),
+            KeyCode::Slash => Some(ControlCommand::Action(ActionCommand::OpenSearch)),
+            KeyCode::KeyV => Some(ControlCommand::Action(ActionCommand::NextMedia)),
+            KeyCode::PageUp => Some(ControlCommand::Action(ActionCommand::ScrollTextUp)),
+            KeyCode::PageDown => Some(ControlCommand::Action(ActionCommand::ScrollTextDown)),
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::golden::{engine, fixture_roms};
+
+    fn app() -> OsirisApp {
+        let missing = std::env::temp_dir().join("osiris-missing");
+        let config = Config {
+            state_file: missing.join("state.xml"),
+            metadata_file: missing.join("metadata.xml"),
+            layout_file: missing.join("layout.xml"),
+            ..Config::default()
+        };
+        let renderer = Renderer::new(engine(), Rotation::None);
+        OsirisApp::new(renderer, fixture_roms(), &config)
+    }
+
+    /// Finishes a rescan that found only the first game of the first system.
+    fn rescan_fewer_games(app: &mut OsirisApp) {
+        let mut library = fixture_roms();
+        library.systems.truncate(1);
+        library.systems[0].games.truncate(1);
+        let (tx, rx) = channel();
+        tx.send(library).unwrap();
+        app.rescan = Some(rx);
+        app.pick_up_rescan();
+    }
+
+    #[test]
+    fn filter_hiding_every_game_clears_the_selection() {
+        let mut app = app();
+        assert!(app.selection.1.is_some());
+        app.library.borrow_mut().systems[0].filter.decade = Some(2050);
+        app.dispatch_ui_event(UiEvent::UserStateChanged);
+        assert_eq!(app.selection, (0, None));
+        assert!(app.screenshot(640, 360).is_some());
+    }
+
+    #[test]
+    fn rescan_during_attract_mode() {
+        let mut app = app();
+        renderer::lay_out(&app.renderer.tui, &mut app.root_panel, 640, 360);
+        app.dispatch_ui_event(UiEvent::Idle);
+        assert!(app.root_panel.modal().is_active());
+
+        rescan_fewer_games(&mut app);
+        assert!(app.root_panel.modal().is_active());
+        assert!(app.screenshot(640, 360).is_some());
+    }
+
+    #[test]
+    fn rescan_during_search() {
+        use ActionCommand::{OpenSearch, Select};
+        use NavigationCommand::{Down, Right};
+
+        let mut app = app();
+        renderer::lay_out(&app.renderer.tui, &mut app.root_panel, 640, 360);
+        let mut press = |cmds: &[ControlCommand]| {
+            for cmd in cmds {
+                app.handle_input(Input::Tap(cmd.clone()));
+            }
+        };
+        let (nav, action) = (ControlCommand::Navigation, ControlCommand::Action);
+        // Type "A", search every system and move down the results
+        press(&[action(OpenSearch), action(Select)]);
+        press(&[nav(Down), nav(Down), nav(Down), nav(Down)]);
+        press(&[nav(Right), nav(Right), nav(Right), action(Select)]);
+        press(&[
+            nav(Down),
+            nav(Down),
+            nav(Down),
+            nav(Down),
+            nav(Down),
+            nav(Down),
+        ]);
+
+        rescan_fewer_games(&mut app);
+        assert!(app.screenshot(640, 360).is_some());
+        app.handle_input(Input::Tap(action(Select)));
+        assert_eq!(app.selection, (0, Some(0)));
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "usage: osiris [--record FILE | --replay FILE]
+       osiris screenshot [--out FILE.png] [--size WIDTHxHEIGHT] [--replay FILE]
+       osiris scrape SYSTEM";
+
+/// Smallest screenshot that still fits the layout's frames.
+const MIN_SIZE: (u32, u32) = (320, 240);
+
+/// What to do, from the command line arguments.
+pub enum Command {
+    /// Open the frontend window, optionally recording the session's inputs
+    /// or playing a recording back.
+    Run {
+        record: Option<PathBuf>,
+        replay: Option<PathBuf>,
+    },
+    /// Render the start screen (or the screen a recording leads to) to a PNG
+    /// file without a display.
+    Screenshot {
+        out: PathBuf,
+        width: u32,
+        height: u32,
+        replay: Option<PathBuf>,
+    },
+    /// Fill in the missing metadata and images of a system's games from
+    /// the configured provider.
+    Scrape { system: String },
+}
+
+impl Command {
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.peekable();
+        let command = args.next_if(|arg| !arg.starts_with("--"));
+        let operand = args.next_if(|arg| !arg.starts_with("--"));
+
+        let mut options = Vec::new();
+        while let Some(option) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", option))?;
+            options.push((option, value));
+        }
+        let unknown = |option: &str| Err(format!("unknown option {:?}", option));
+
+        match (command.as_deref(), operand) {
+            (Some("scrape"), Some(system)) => match options.first() {
+                Some((option, _)) => unknown(option),
+                None => Ok(Command::Scrape { system }),
+            },
+            (Some("scrape"), None) => Err("scrape needs the system to scrape, e.g. NES".into()),
+            (_, Some(operand)) => Err(format!("unexpected argument {:?}", operand)),
+            (None, None) => {
+                let (mut record, mut replay) = (None, None);
+                for (option, value) in options {
+                    match option.as_str() {
+                        "--record" => record = Some(PathBuf::from(value)),
+                        "--replay" => replay = Some(PathBuf::from(value)),
+                        _ => return unknown(&option),
+                    }
+                }
+                if record.is_some() && replay.is_some() {
+                    return Err("--record and --replay can not be combined".into());
+                }
+                Ok(Command::Run { record, replay })
+            }
+            (Some("screenshot"), None) => {
+                let mut out = PathBuf::from("screenshot.png");
+                let (mut width, mut height) = (1920, 1080);
+                let mut replay = None;
+                for (option, value) in options {
+                    match option.as_str() {
+                        "--out" => out = PathBuf::from(value),
+                        "--size" => (width, height) = parse_size(&value)?,
+                        "--replay" => replay = Some(PathBuf::from(value)),
+                        _ => return unknown(&option),
+                    }
+                }
+                Ok(Command::Screenshot {
+                    out,
+                    width,
+                    height,
+                    replay,
+                })
+            }
+            (Some(other), None) => Err(format!("unknown command {:?}", other)),
+        }
+    }
+}
+
+/// `WIDTHxHEIGHT`, e.g. `1920x1080`.
+fn parse_size(size: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("invalid size {:?}, expected e.g. 1920x1080", size);
+    let (width, height) = size.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let width: u32 = width.trim().parse().map_err(|_| invalid())?;
+    let height: u32 = height.trim().parse().map_err(|_| invalid())?;
+    if width < MIN_SIZE.0 || height < MIN_SIZE.1 {
+        return Err(format!(
+            "size {}x{} is below the minimum of {}x{}",
+            width, height, MIN_SIZE.0, MIN_SIZE.1
+        ));
+    }
+    Ok((width, height))
+}
@@ -1,3 +1,6 @@
+use std::fmt;
+use std::str::FromStr;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ControlCommand {
     Navigation(NavigationCommand),
@@ -17,12 +20,131 @@ pub enum NavigationCommand {
 pub enum ActionCommand {
     Select,
     Back,
+    ToggleOneGameOneRom,
+    NextAlternate,
+    CycleSort,
+    OpenFilters,
+    ToggleFavorite,
+    OpenSearch,
+    NextMedia,
+    ScrollTextUp,
+    ScrollTextDown,
+}
+
+/// Names used in input recordings, e.g. `down` or `open-search`.
+const COMMAND_NAMES: [(&str, ControlCommand); 15] = [
+    ("up", ControlCommand::Navigation(NavigationCommand::Up)),
+    ("down", ControlCommand::Navigation(NavigationCommand::Down)),
+    ("left", ControlCommand::Navigation(NavigationCommand::Left)),
+    (
+        "right",
+        ControlCommand::Navigation(NavigationCommand::Right),
+    ),
+    ("select", ControlCommand::Action(ActionCommand::Select)),
+    ("back", ControlCommand::Action(ActionCommand::Back)),
+    (
+        "toggle-1g1r",
+        ControlCommand::Action(ActionCommand::ToggleOneGameOneRom),
+    ),
+    (
+        "next-alternate",
+        ControlCommand::Action(ActionCommand::NextAlternate),
+    ),
+    (
+        "cycle-sort",
+        ControlCommand::Action(ActionCommand::CycleSort),
+    ),
+    (
+        "open-filters",
+        ControlCommand::Action(ActionCommand::OpenFilters),
+    ),
+    (
+        "toggle-favorite",
+        ControlCommand::Action(ActionCommand::ToggleFavorite),
+    ),
+    (
+        "open-search",
+        ControlCommand::Action(ActionCommand::OpenSearch),
+    ),
+    (
+        "next-media",
+        ControlCommand::Action(ActionCommand::NextMedia),
+    ),
+    (
+        "scroll-text-up",
+        ControlCommand::Action(ActionCommand::ScrollTextUp),
+    ),
+    (
+        "scroll-text-down",
+        ControlCommand::Action(ActionCommand::ScrollTextDown),
+    ),
+];
+
+impl fmt::Display for ControlCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = COMMAND_NAMES
+            .iter()
+            .find(|(_, cmd)| cmd == self)
+            .map_or("none", |(name, _)| name);
+        f.write_str(name)
+    }
+}
+
+impl FromStr for ControlCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        COMMAND_NAMES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s.trim()))
+            .map(|(_, cmd)| cmd.clone())
+            .ok_or_else(|| format!("unknown command {:?}", s))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum UiEvent {
     None,
     SystemChanged(usize),
-    GameChanged(usize),
+    /// The game selected in the list; `None` when no game is listed.
+    GameChanged(Option<usize>),
     LaunchGame(usize, usize),
+    UserStateChanged,
+    SearchChanged(String),
+    JumpToGame(usize, usize),
+    Idle,
+    /// Decoded images arrived from the loader threads.
+    ImagesLoaded,
+    /// Game metadata or media were edited from outside the controls.
+    LibraryChanged,
+    /// A rescan replaced the whole library; every index into the old one is
+    /// stale. Carries the system to show, which is followed by a
+    /// `JumpToGame` when the selected game is still there.
+    LibraryReplaced(usize),
+    /// Events raised by several widgets for the same event, dispatched in
+    /// turn.
+    Batch(Vec<UiEvent>),
+}
+
+impl UiEvent {
+    /// Combines the events raised by two parts of the tree so neither is
+    /// lost.
+    pub fn and(self, other: UiEvent) -> UiEvent {
+        match (self, other) {
+            (UiEvent::None, event) | (event, UiEvent::None) => event,
+            (UiEvent::Batch(mut events), UiEvent::Batch(more)) => {
+                events.extend(more);
+                UiEvent::Batch(events)
+            }
+            (UiEvent::Batch(mut events), event) => {
+                events.push(event);
+                UiEvent::Batch(events)
+            }
+            (event, UiEvent::Batch(mut events)) => {
+                events.insert(0, event);
+                UiEvent::Batch(events)
+            }
+            (first, second) => UiEvent::Batch(vec![first, second]),
+        }
+    }
 }
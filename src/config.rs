@@ -0,0 +1,171 @@
+use crate::models::Cabinet;
+use crate::ui::tui::{DEFAULT_GRID_ROWS, GridSize, Rotation};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// User settings read from `osiris.xml`, e.g.
+///
+/// ```xml
+/// <osiris>
+///   <region>USA</region>
+///   <region>Europe</region>
+///   <one_game_one_rom>true</one_game_one_rom>
+/// </osiris>
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Preferred regions, best first, used to pick the 1G1R copy of a title.
+    #[serde(rename = "region")]
+    pub region_priority: Vec<String>,
+    /// Start the game list in 1G1R (one game, one ROM) mode.
+    pub one_game_one_rom: bool,
+    /// Directory holding one subdirectory of ROMs per system.
+    pub roms_dir: PathBuf,
+    /// Where sort modes and play history are kept between sessions.
+    pub state_file: PathBuf,
+    /// Where game metadata edited through the web interface is kept.
+    pub metadata_file: PathBuf,
+    /// How many decoded images to keep in memory.
+    pub image_cache_size: usize,
+    /// Colors, border glyphs and texts of the UI.
+    pub theme_file: PathBuf,
+    /// Arrangement of the carousel, game list and details panels.
+    pub layout_file: PathBuf,
+    /// Text rows on screen; the font scales to fit.
+    pub grid_rows: usize,
+    /// Font size in pixels. When set, the row count follows the screen
+    /// height instead of `grid_rows`.
+    pub font_size: Option<f32>,
+    /// Clockwise rotation of the UI in degrees (0, 90, 180 or 270).
+    pub rotation: Rotation,
+    /// Also turn the directional controls with the UI, for controls that
+    /// are mounted to (and rotate with) the screen.
+    pub rotate_controls: bool,
+    /// Minutes without input before attract mode starts (0 disables it).
+    pub attract_minutes: u64,
+    /// Seconds each game is shown in attract mode.
+    pub attract_slide_seconds: u64,
+    /// Darken the attract mode slideshow to spare the screen.
+    pub attract_dim: bool,
+    /// Unix socket for the JSON-lines control API (unset disables it).
+    pub control_socket: Option<PathBuf>,
+    /// Address of the web management interface, e.g. `127.0.0.1:8080`
+    /// (unset disables it).
+    pub web_address: Option<String>,
+    /// Metadata provider used by `osiris scrape`.
+    pub scraper: ScraperConfig,
+    /// Support files adding to what MAME says about its games.
+    pub mame: MameConfig,
+    /// Controls of the cabinet, to dim or hide the games it can't play
+    /// (unset shows every game as playable).
+    pub cabinet: Option<Cabinet>,
+}
+
+/// The `<mame>` section: optional support files for the arcade games,
+/// matched by machine id.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MameConfig {
+    /// Arcade History's `history.xml` (or the older `history.dat`).
+    pub history_file: Option<PathBuf>,
+    /// `mameinfo.dat`, with driver notes and known issues.
+    pub mameinfo_file: Option<PathBuf>,
+    /// Progetto-SNAPS `catver.ini`: genres and the mature flag.
+    pub catver_file: Option<PathBuf>,
+    /// `nplayers.ini`: player counts and whether they play at once.
+    pub nplayers_file: Option<PathBuf>,
+    /// `controls.dat` (ini flavor): button labels and, where MAME says
+    /// nothing, the controls.
+    pub controls_file: Option<PathBuf>,
+}
+
+/// The `<scraper>` section: a ScreenScraper compatible provider and the
+/// account to query it with.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScraperConfig {
+    /// API root, e.g. `https://api.screenscraper.fr/api2`.
+    pub base_url: String,
+    /// Developer credentials of the provider's API.
+    pub dev_id: String,
+    pub dev_password: String,
+    /// User account, for higher quotas (optional).
+    pub user: Option<String>,
+    pub password: Option<String>,
+    /// Language of descriptions and genres (`en`, `fr`, `de`...).
+    pub language: String,
+    /// Least time between two requests to the provider.
+    pub interval_ms: u64,
+    /// Where answers are kept, so games are not looked up twice.
+    pub cache_dir: PathBuf,
+}
+
+impl Default for ScraperConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "https://api.screenscraper.fr/api2".into(),
+            dev_id: String::new(),
+            dev_password: String::new(),
+            user: None,
+            password: None,
+            language: "en".into(),
+            interval_ms: 1200,
+            cache_dir: PathBuf::from("./scraper-cache"),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            region_priority: vec![
+                "USA".into(),
+                "World".into(),
+                "Europe".into(),
+                "Japan".into(),
+            ],
+            one_game_one_rom: false,
+            roms_dir: PathBuf::from("./roms"),
+            state_file: PathBuf::from("./osiris-state.xml"),
+            metadata_file: PathBuf::from("./osiris-metadata.xml"),
+            image_cache_size: 32,
+            theme_file: PathBuf::from("./theme.xml"),
+            layout_file: PathBuf::from("./layout.xml"),
+            grid_rows: DEFAULT_GRID_ROWS,
+            font_size: None,
+            rotation: Rotation::None,
+            rotate_controls: false,
+            attract_minutes: 5,
+            attract_slide_seconds: 10,
+            attract_dim: false,
+            control_socket: None,
+            web_address: None,
+            scraper: ScraperConfig::default(),
+            mame: MameConfig::default(),
+            cabinet: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let Ok(xml) = fs::read_to_string(path.as_ref()) else {
+            return Self::default();
+        };
+
+        match quick_xml::de::from_str(&xml) {
+            Ok(config) => config,
+            Err(e) => {
+                println!("⚠️ OSIRIS: CONFIG PARSE ERROR: {}. USING DEFAULTS.", e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn grid_size(&self) -> GridSize {
+        self.font_size
+            .map_or(GridSize::Rows(self.grid_rows), GridSize::FontSize)
+    }
+}
@@ -0,0 +1,264 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+
+/// A request from a control client, one JSON object per line:
+///
+/// ```text
+/// {"cmd": "status"}
+/// {"cmd": "command", "command": "down"}
+/// {"cmd": "launch"}
+/// {"cmd": "launch", "system": 0, "game": 12}
+/// {"cmd": "subscribe"}
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+pub enum Request {
+    /// Reply with the selected system and game.
+    Status,
+    /// Press and release a control, by its recording name (`down`, `select`).
+    Command { command: String },
+    /// Launch the selected game, or the given one.
+    Launch {
+        system: Option<usize>,
+        game: Option<usize>,
+    },
+    /// Receive selection changes and launches as they happen.
+    Subscribe,
+}
+
+/// Where to send the reply to a request.
+pub struct Client {
+    tx: Sender<String>,
+}
+
+impl Client {
+    pub fn reply(&self, reply: Value) {
+        // A client that hung up no longer needs an answer
+        let _ = self.tx.send(reply.to_string());
+    }
+}
+
+/// JSON-lines server on a Unix domain socket, for coin mechs, light
+/// controllers and the like. Connections are served on background threads;
+/// the app picks up their requests with `poll` and answers them in between
+/// frames, so they see the same state as the controls.
+pub struct ControlServer {
+    path: PathBuf,
+    requests: Receiver<(Request, Client)>,
+    subscribers: Vec<Sender<String>>,
+}
+
+impl ControlServer {
+    /// Listens on `path`; `wake` is called whenever a request comes in.
+    pub fn bind<W>(path: &Path, wake: W) -> io::Result<Self>
+    where
+        W: Fn() + Clone + Send + 'static,
+    {
+        // A socket left behind by a crashed instance refuses connections
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is in use", path.display()),
+                ));
+            }
+            std::fs::remove_file(path)?;
+        }
+
+        let listener = UnixListener::bind(path)?;
+        let (tx, requests) = channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (tx, wake) = (tx.clone(), wake.clone());
+                thread::spawn(move || serve(stream, tx, wake));
+            }
+        });
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            requests,
+            subscribers: Vec::new(),
+        })
+    }
+
+    /// The next request for the app to answer. Subscriptions are handled
+    /// here.
+    pub fn poll(&mut self) -> Option<(Request, Client)> {
+        loop {
+            let (request, client) = self.requests.try_recv().ok()?;
+            if request != Request::Subscribe {
+                return Some((request, client));
+            }
+            client.reply(json!({ "ok": true }));
+            self.subscribers.push(client.tx);
+        }
+    }
+
+    /// Sends `event` to every subscriber still connected.
+    pub fn notify(&mut self, event: Value) {
+        let line = event.to_string();
+        self.subscribers.retain(|tx| tx.send(line.clone()).is_ok());
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Reads the requests of one connection; replies and events are written by
+/// a second thread so a slow client never holds up the app.
+fn serve<W: Fn()>(stream: UnixStream, requests: Sender<(Request, Client)>, wake: W) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let (tx, outgoing) = channel::<String>();
+    thread::spawn(move || {
+        for line in outgoing {
+            if writeln!(writer, "{}", line).is_err() {
+                break;
+            }
+        }
+    });
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let client = Client { tx: tx.clone() };
+                if requests.send((request, client)).is_err() {
+                    break;
+                }
+                wake();
+            }
+            Err(e) => {
+                let _ = tx.send(json!({ "ok": false, "error": e.to_string() }).to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("osiris-{}-{}.sock", name, std::process::id()))
+    }
+
+    fn next_request(server: &mut ControlServer) -> (Request, Client) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(request) = server.poll() {
+                return request;
+            }
+            assert!(Instant::now() < deadline, "no request arrived");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn read_json(reader: &mut impl BufRead) -> Value {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn requests_are_answered_and_events_delivered() {
+        let path = socket_path("control");
+        let mut server = ControlServer::bind(&path, || {}).unwrap();
+        let mut client = UnixStream::connect(&path).unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+
+        writeln!(client, r#"{{"cmd": "command", "command": "down"}}"#).unwrap();
+        let (request, reply_to) = next_request(&mut server);
+        assert_eq!(
+            request,
+            Request::Command {
+                command: "down".into()
+            }
+        );
+        reply_to.reply(json!({ "ok": true }));
+        assert_eq!(read_json(&mut reader), json!({ "ok": true }));
+
+        writeln!(client, "not json").unwrap();
+        assert_eq!(read_json(&mut reader)["ok"], json!(false));
+
+        // Subscribing is answered by the server itself
+        writeln!(client, r#"{{"cmd": "subscribe"}}"#).unwrap();
+        writeln!(client, r#"{{"cmd": "status"}}"#).unwrap();
+        assert_eq!(next_request(&mut server).0, Request::Status);
+        assert_eq!(read_json(&mut reader), json!({ "ok": true }));
+
+        server.notify(json!({ "event": "game_changed", "game": 3 }));
+        assert_eq!(read_json(&mut reader)["game"], json!(3));
+
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn app_answers_a_local_client() {
+        use crate::app::OsirisApp;
+        use crate::config::Config;
+        use crate::ui::golden;
+        use crate::ui::renderer::Renderer;
+        use crate::ui::tui::Rotation;
+
+        let path = socket_path("app");
+        let config = Config {
+            state_file: std::env::temp_dir().join("osiris-control-test-state.xml"),
+            ..Config::default()
+        };
+        let renderer = Renderer::new(golden::engine(), Rotation::None);
+        let mut app = OsirisApp::new(renderer, golden::fixture_roms(), &config);
+        app.serve_control(ControlServer::bind(&path, || {}).unwrap());
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        let mut ask = |request: &str| {
+            writeln!(client, "{}", request).unwrap();
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let mut line = String::new();
+            while line.is_empty() {
+                assert!(Instant::now() < deadline, "no reply to {}", request);
+                app.answer_control_requests();
+                reader
+                    .get_mut()
+                    .set_read_timeout(Some(Duration::from_millis(10)))
+                    .unwrap();
+                let _ = reader.read_line(&mut line);
+            }
+            serde_json::from_str::<Value>(&line).unwrap()
+        };
+
+        let status = ask(r#"{"cmd": "status"}"#);
+        assert_eq!(status["system"]["name"], json!("MAME"));
+        assert_eq!(status["game"]["name"], json!("Bubble Bobble"));
+
+        assert_eq!(
+            ask(r#"{"cmd": "command", "command": "down"}"#)["ok"],
+            json!(true)
+        );
+        let status = ask(r#"{"cmd": "status"}"#);
+        assert_eq!(status["game"]["name"], json!("Galaxian"));
+
+        assert_eq!(
+            ask(r#"{"cmd": "command", "command": "jump"}"#)["ok"],
+            json!(false)
+        );
+        assert_eq!(ask(r#"{"cmd": "launch", "system": 9}"#)["ok"], json!(false));
+    }
+}
@@ -0,0 +1,472 @@
+use crate::config::MameConfig;
+use crate::models::{Control, ControlKind, Game, RomLibrary};
+use crate::storage::parse_ways;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// `history.xml`: one entry per text, listing the machines it is about.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct HistoryXml {
+    entry: Vec<HistoryEntry>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct HistoryEntry {
+    systems: HistorySystems,
+    text: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct HistorySystems {
+    system: Vec<HistorySystem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistorySystem {
+    #[serde(rename = "@name")]
+    name: String,
+}
+
+/// A game's section of `controls.dat`.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct PanelEntry {
+    /// Player 1's button labels, by button number.
+    labels: Vec<String>,
+    /// Player 1's controls, for games MAME did not describe.
+    controls: Vec<Control>,
+}
+
+/// Adds what the configured support files say to the MAME games. A clone
+/// without an entry of its own gets its parent's.
+pub fn apply(library: &mut RomLibrary, config: &MameConfig) {
+    let Some(system) = library.systems.iter_mut().find(|s| s.name == "MAME") else {
+        return;
+    };
+    let wanted: HashSet<&str> = system
+        .games
+        .iter()
+        .flat_map(|g| std::iter::once(g.id.as_str()).chain(g.clone_of.as_deref()))
+        .collect();
+
+    let history = config
+        .history_file
+        .as_deref()
+        .and_then(|path| read_history(path, &wanted));
+    let mame_info = config
+        .mameinfo_file
+        .as_deref()
+        .and_then(read_text)
+        .map(|text| parse_dat(&text, "mame", &wanted));
+    let categories = config
+        .catver_file
+        .as_deref()
+        .and_then(read_text)
+        .map(|text| parse_ini(&text, "Category", &wanted));
+    let players = config
+        .nplayers_file
+        .as_deref()
+        .and_then(read_text)
+        .map(|text| parse_ini(&text, "NPlayers", &wanted));
+    let panels = config
+        .controls_file
+        .as_deref()
+        .and_then(read_text)
+        .map(|text| parse_controls_dat(&text, &wanted));
+
+    for game in &mut system.games {
+        if let Some(texts) = &history {
+            game.history = lookup(texts, game);
+        }
+        if let Some(texts) = &mame_info {
+            game.mame_info = lookup(texts, game);
+        }
+        if let Some(category) = categories.as_ref().and_then(|c| lookup(c, game)) {
+            (game.genres, game.mature) = parse_category(&category);
+        }
+        if let Some((max, simultaneous)) = players
+            .as_ref()
+            .and_then(|p| lookup(p, game))
+            .and_then(|value| parse_nplayers(&value))
+        {
+            game.max_players = max.max(game.min_players);
+            game.simultaneous_players = Some(simultaneous);
+        }
+        if let Some(panel) = panels.as_ref().and_then(|p| lookup(p, game)) {
+            game.button_labels = panel.labels;
+            if game.controls.is_empty() {
+                game.controls = panel.controls;
+            }
+        }
+    }
+}
+
+fn lookup<T: Clone>(entries: &HashMap<String, T>, game: &Game) -> Option<T> {
+    entries
+        .get(&game.id)
+        .or_else(|| entries.get(game.clone_of.as_ref()?))
+        .cloned()
+}
+
+/// The file as text; older support files are not always valid UTF-8.
+fn read_text(path: &Path) -> Option<String> {
+    match fs::read(path) {
+        Ok(data) => Some(String::from_utf8_lossy(&data).into_owned()),
+        Err(e) => {
+            println!(
+                "⚠️ OSIRIS: COULD NOT READ {}: {}. IGNORING IT.",
+                path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Arcade History texts of the `wanted` machines, from `history.xml` or
+/// the older `history.dat`.
+fn read_history(path: &Path, wanted: &HashSet<&str>) -> Option<HashMap<String, String>> {
+    let text = read_text(path)?;
+    if !text.trim_start().starts_with('<') {
+        return Some(parse_dat(&text, "bio", wanted));
+    }
+
+    match parse_history_xml(&text, wanted) {
+        Ok(texts) => Some(texts),
+        Err(e) => {
+            println!("⚠️ OSIRIS: HISTORY FILE PARSE ERROR: {}. IGNORING IT.", e);
+            None
+        }
+    }
+}
+
+fn parse_history_xml(
+    xml: &str,
+    wanted: &HashSet<&str>,
+) -> Result<HashMap<String, String>, quick_xml::DeError> {
+    let history: HistoryXml = quick_xml::de::from_str(xml)?;
+    let mut texts = HashMap::new();
+    for entry in history.entry {
+        let text = trim_blank_lines(&entry.text);
+        for system in entry.systems.system {
+            if wanted.contains(system.name.as_str()) {
+                texts.insert(system.name, text.clone());
+            }
+        }
+    }
+    Ok(texts)
+}
+
+/// The `section` texts of the `wanted` machines in a `.dat` file made of
+///
+/// ```text
+/// $info=pacman,puckman,
+/// $bio
+/// ...
+/// $end
+/// ```
+///
+/// blocks (`$bio` in `history.dat`, `$mame` in `mameinfo.dat`).
+fn parse_dat(text: &str, section: &str, wanted: &HashSet<&str>) -> HashMap<String, String> {
+    let mut texts = HashMap::new();
+    let mut ids: Vec<&str> = Vec::new();
+    let mut body: Option<Vec<&str>> = None;
+
+    for line in text.lines() {
+        let line = line.trim_end();
+        if let Some(list) = line.strip_prefix("$info=") {
+            ids = list
+                .split(',')
+                .map(str::trim)
+                .filter(|id| wanted.contains(id))
+                .collect();
+            body = None;
+        } else if line == "$end" {
+            if let Some(lines) = body.take() {
+                let text = trim_blank_lines(&lines.join("\n"));
+                for id in &ids {
+                    texts.insert(id.to_string(), text.clone());
+                }
+            }
+            ids.clear();
+        } else if let Some(name) = line.strip_prefix('$').filter(|_| body.is_none()) {
+            // Software list entries (`$nes=...`) are not machines
+            if name.contains('=') {
+                ids.clear();
+            } else if name == section && !ids.is_empty() {
+                body = Some(Vec::new());
+            }
+        } else if let Some(lines) = &mut body {
+            lines.push(line);
+        }
+    }
+    texts
+}
+
+/// The `key=value` lines of the `[section]` of an `.ini` file, for the
+/// `wanted` keys.
+fn parse_ini(text: &str, section: &str, wanted: &HashSet<&str>) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    let mut in_section = false;
+    for line in text.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_section = name.eq_ignore_ascii_case(section);
+        } else if in_section
+            && !line.starts_with(';')
+            && let Some((key, value)) = line.split_once('=')
+            && wanted.contains(key.trim())
+        {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    values
+}
+
+/// A `catver.ini` category such as "Maze / Collect * Mature *" as genres
+/// (the top category, then the full one) and the mature flag.
+fn parse_category(category: &str) -> (Vec<String>, bool) {
+    let mature = category.contains("* Mature *");
+    let category = category.replace("* Mature *", "").trim().to_string();
+    let top = category
+        .split(" / ")
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_string();
+    let genres = if top == category {
+        vec![category]
+    } else {
+        vec![top, category]
+    };
+    (genres, mature)
+}
+
+/// An `nplayers.ini` value such as "2P alt" or "4P alt / 2P sim" as the
+/// most players and the most of them playing at once. `None` for entries
+/// that are not player counts ("BIOS", "Pinball", "???"...).
+fn parse_nplayers(value: &str) -> Option<(u8, u8)> {
+    let mut max = 0;
+    let mut simultaneous = 1;
+    for mode in value.split('/') {
+        let mut words = mode.split_whitespace();
+        let players: u8 = words.next()?.strip_suffix('P')?.parse().ok()?;
+        max = max.max(players);
+        if words.next() != Some("alt") {
+            simultaneous = simultaneous.max(players);
+        }
+    }
+    (max > 0).then_some((max, simultaneous))
+}
+
+/// The `[game]` sections of a `controls.dat` (ini flavor) such as
+///
+/// ```text
+/// [sf2]
+/// P1NumButtons=6
+/// P1Controls=8-way Joystick+joy8way
+/// P1_BUTTON1=Jab Punch
+/// ```
+fn parse_controls_dat(text: &str, wanted: &HashSet<&str>) -> HashMap<String, PanelEntry> {
+    let mut panels: HashMap<String, PanelEntry> = HashMap::new();
+    let mut current: Option<&mut PanelEntry> = None;
+    let mut buttons = 0;
+
+    for line in text.lines().map(str::trim) {
+        if let Some(id) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = wanted
+                .contains(id)
+                .then(|| panels.entry(id.to_string()).or_default());
+            buttons = 0;
+            continue;
+        }
+        let (Some(panel), Some((key, value))) = (current.as_deref_mut(), line.split_once('='))
+        else {
+            continue;
+        };
+        let value = value.trim();
+
+        if key == "P1NumButtons" {
+            buttons = value.parse().unwrap_or(0);
+            for control in &mut panel.controls {
+                control.buttons = buttons;
+            }
+        } else if key == "P1Controls" {
+            // "Trackball+trackball|Just Buttons+button": names and codes
+            panel.controls = value
+                .split('|')
+                .filter_map(|device| parse_control_code(device.rsplit('+').next()?))
+                .map(|(kind, ways)| Control {
+                    kind,
+                    buttons,
+                    ways,
+                    ..Control::default()
+                })
+                .collect();
+        } else if let Some(n) = key.strip_prefix("P1_BUTTON")
+            && let Ok(n @ 1..=32) = n.parse::<usize>()
+        {
+            if panel.labels.len() < n {
+                panel.labels.resize(n, String::new());
+            }
+            panel.labels[n - 1] = value.to_string();
+        }
+    }
+    panels
+}
+
+/// A `controls.dat` device code ("joy8way", "vjoy2way", "trackball"...).
+fn parse_control_code(code: &str) -> Option<(ControlKind, Option<u8>)> {
+    let code = code.trim();
+    let kind = if code.starts_with("doublejoy") {
+        ControlKind::DoubleJoystick
+    } else if code.starts_with("joy") || code.starts_with("vjoy") {
+        ControlKind::Joystick
+    } else if code == "button" {
+        ControlKind::Buttons
+    } else {
+        ControlKind::from_mame(code)?
+    };
+    Some((kind, parse_ways(code)))
+}
+
+fn trim_blank_lines(text: &str) -> String {
+    text.lines()
+        .skip_while(|line| line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_end()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dat_blocks_are_keyed_by_machine() {
+        let dat = "\
+## comment
+$info=pacman,puckman,
+$bio
+
+Pac-Man (c) 1980 Namco.
+
+- TRIVIA -
+$end
+$nes=pacman,
+$bio
+Not the arcade one.
+$end
+$info=galaga,
+$drv
+Driver notes.
+$end
+";
+        let wanted = HashSet::from(["pacman", "puckman", "galaga"]);
+        let texts = parse_dat(dat, "bio", &wanted);
+        assert_eq!(texts.len(), 2);
+        assert_eq!(texts["puckman"], "Pac-Man (c) 1980 Namco.\n\n- TRIVIA -");
+        assert_eq!(texts["pacman"], texts["puckman"]);
+    }
+
+    #[test]
+    fn clones_fall_back_to_their_parent() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<history version="2.60">
+  <entry>
+    <systems><system name="sf2"/><system name="sf2ua"/></systems>
+    <text>
+Street Fighter II &amp; friends.
+</text>
+  </entry>
+  <entry>
+    <software><item list="snes" name="sf2"/></software>
+    <text>Console port.</text>
+  </entry>
+</history>"#;
+        let wanted = HashSet::from(["sf2", "sf2ua"]);
+        let texts = parse_history_xml(xml, &wanted).unwrap();
+        assert_eq!(texts["sf2"], "Street Fighter II & friends.");
+
+        let mut clone = Game {
+            id: "sf2ce".into(),
+            clone_of: Some("sf2".into()),
+            ..Game::default()
+        };
+        assert_eq!(lookup(&texts, &clone), Some(texts["sf2"].clone()));
+        clone.clone_of = None;
+        assert_eq!(lookup(&texts, &clone), None);
+    }
+
+    #[test]
+    fn catver_and_nplayers_fill_genres_and_players() {
+        let catver = "\
+;; catver.ini
+[Category]
+pacman=Maze / Collect
+mjgirl=Tabletop / Mahjong * Mature *
+
+[VerAdded]
+pacman=0.30
+";
+        let nplayers = "\
+[NPlayers]
+pacman=2P alt
+sf2=2P sim
+gauntlet=4P alt / 2P sim
+neogeo=BIOS
+";
+        let wanted = HashSet::from(["pacman", "mjgirl", "sf2", "gauntlet", "neogeo"]);
+        let categories = parse_ini(catver, "Category", &wanted);
+        assert_eq!(categories.len(), 2);
+        assert_eq!(
+            parse_category(&categories["pacman"]),
+            (vec!["Maze".into(), "Maze / Collect".into()], false)
+        );
+        assert_eq!(
+            parse_category(&categories["mjgirl"]),
+            (vec!["Tabletop".into(), "Tabletop / Mahjong".into()], true)
+        );
+
+        let players = parse_ini(nplayers, "NPlayers", &wanted);
+        assert_eq!(parse_nplayers(&players["pacman"]), Some((2, 1)));
+        assert_eq!(parse_nplayers(&players["sf2"]), Some((2, 2)));
+        assert_eq!(parse_nplayers(&players["gauntlet"]), Some((4, 2)));
+        assert_eq!(parse_nplayers(&players["neogeo"]), None);
+    }
+
+    #[test]
+    fn controls_dat_labels_the_buttons() {
+        let dat = "\
+[sf2]
+gamename=Street Fighter II
+P1NumButtons=6
+P1Controls=8-way Joystick+joy8way
+P1_BUTTON1=Jab Punch
+P1_BUTTON3=Fierce Punch
+P1_JOYSTICK_UP=Up
+
+[marble]
+P1NumButtons=1
+P1Controls=Trackball+trackball|Just Buttons+button
+";
+        let wanted = HashSet::from(["sf2", "marble"]);
+        let panels = parse_controls_dat(dat, &wanted);
+        assert_eq!(panels["sf2"].labels, ["Jab Punch", "", "Fierce Punch"]);
+        assert_eq!(
+            panels["sf2"].controls,
+            [Control {
+                kind: ControlKind::Joystick,
+                buttons: 6,
+                ways: Some(8),
+                ..Control::default()
+            }]
+        );
+        let kinds: Vec<_> = panels["marble"].controls.iter().map(|c| c.kind).collect();
+        assert_eq!(kinds, [ControlKind::Trackball, ControlKind::Buttons]);
+    }
+}
@@ -1,28 +1,116 @@
 mod app;
+mod cli;
 mod commands;
+mod config;
+#[cfg(unix)]
+mod control;
+mod dats;
+mod metadata;
 mod models;
+mod replay;
+mod scraper;
+mod state;
 mod storage;
 mod ui;
+mod web;
 
 use crate::ui::{renderer, tui};
 use winit::event_loop::{ControlFlow, EventLoop};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let event_loop = EventLoop::new()?;
-
-    let tui_instance =
-        ui::tui::TuiEngine::new(include_bytes!("../fonts/JetBrainsMono-Regular.ttf"));
+    let command = match cli::Command::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("OSIRIS: {}\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    let config = config::Config::load("./osiris.xml");
 
     println!("OSIRIS: INITIATING ROM SCAN...");
-    let library = storage::scan_roms("./roms");
+    let library = storage::load_library(&config.roms_dir, &config.mame);
     println!(
         "OSIRIS: SCAN COMPLETE. SYSTEMS DETECTED: {}",
         library.systems.len()
     );
 
-    let mut app = app::OsirisApp::new(renderer::Renderer::new(tui_instance), library);
-    event_loop.set_control_flow(ControlFlow::Wait);
-    event_loop.run_app(&mut app)?;
+    let build_app = |library| {
+        let tui_instance = ui::tui::TuiEngine::new(
+            include_bytes!("../fonts/JetBrainsMono-Regular.ttf"),
+            ui::theme::Theme::load(&config.theme_file),
+            config.grid_size(),
+        );
+        app::OsirisApp::new(
+            renderer::Renderer::new(tui_instance, config.rotation),
+            library,
+            &config,
+        )
+    };
+
+    match command {
+        cli::Command::Run { record, replay } => {
+            let mut app = build_app(library);
+            if let Some(path) = record {
+                app.record(replay::Recorder::create(&path)?);
+                println!("OSIRIS: RECORDING INPUT TO {}", path.display());
+            }
+            if let Some(path) = replay {
+                app.replay(replay::Replay::load(&path)?);
+                println!("OSIRIS: REPLAYING {}", path.display());
+            }
+            let event_loop = EventLoop::new()?;
+            #[cfg(unix)]
+            if let Some(path) = &config.control_socket {
+                let proxy = event_loop.create_proxy();
+                match control::ControlServer::bind(path, move || {
+                    let _ = proxy.send_event(());
+                }) {
+                    Ok(server) => {
+                        app.serve_control(server);
+                        println!("OSIRIS: CONTROL SOCKET LISTENING ON {}", path.display());
+                    }
+                    Err(e) => println!(
+                        "⚠️ OSIRIS: CONTROL SOCKET ERROR: {}. CONTINUING WITHOUT IT.",
+                        e
+                    ),
+                }
+            }
+            if let Some(address) = &config.web_address {
+                let proxy = event_loop.create_proxy();
+                match web::WebServer::bind(address, move || {
+                    let _ = proxy.send_event(());
+                }) {
+                    Ok(server) => {
+                        println!("OSIRIS: WEB INTERFACE AT http://{}/", server.address());
+                        app.serve_web(server);
+                    }
+                    Err(e) => println!(
+                        "⚠️ OSIRIS: WEB INTERFACE ERROR: {}. CONTINUING WITHOUT IT.",
+                        e
+                    ),
+                }
+            }
+            event_loop.set_control_flow(ControlFlow::Wait);
+            event_loop.run_app(&mut app)?;
+        }
+        cli::Command::Screenshot {
+            out,
+            width,
+            height,
+            replay,
+        } => {
+            let mut app = build_app(library);
+            if let Some(path) = replay {
+                app.replay(replay::Replay::load(&path)?);
+            }
+            let frame = app
+                .screenshot(width, height)
+                .ok_or("could not allocate the screenshot")?;
+            frame.save_png(&out)?;
+            println!("OSIRIS: SCREENSHOT SAVED TO {}", out.display());
+        }
+        cli::Command::Scrape { system } => scraper::run(&config, library, &system)?,
+    }
 
     Ok(())
 }
@@ -0,0 +1,217 @@
+use crate::models::{Game, MediaPaths, ReleaseDate, RomLibrary};
+use crate::storage::parse_name_tags;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Game metadata edited by hand, kept in the metadata file and laid over
+/// what the scan finds, keyed by system and game id like the user state.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataOverrides {
+    pub system: Vec<SystemMetadata>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SystemMetadata {
+    #[serde(rename = "@name")]
+    pub name: String,
+    pub game: Vec<GameMetadata>,
+}
+
+/// The fields of a game that were set by hand; the rest keep their scanned
+/// values. Also the body of an edit request, where only the fields sent
+/// change.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameMetadata {
+    #[serde(rename = "@id", skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<ReleaseDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub developer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+    #[serde(
+        rename = "genre",
+        alias = "genres",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub genres: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_players: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_players: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<f32>, // 0.0 - 1.0
+    pub media: MediaPaths,
+}
+
+impl GameMetadata {
+    pub fn apply(&self, game: &mut Game) {
+        if let Some(name) = &self.name {
+            (game.title, game.regions, game.revision) = parse_name_tags(name);
+            game.name = name.clone();
+        }
+        if self.release_date.is_some() {
+            game.release_date = self.release_date;
+        }
+        if self.developer.is_some() {
+            game.developer = self.developer.clone();
+        }
+        if self.publisher.is_some() {
+            game.publisher = self.publisher.clone();
+        }
+        if !self.genres.is_empty() {
+            game.genres = self.genres.clone();
+        }
+        if self.description.is_some() {
+            game.description = self.description.clone();
+        }
+        if let Some(min) = self.min_players {
+            game.min_players = min.max(1);
+        }
+        if let Some(max) = self.max_players {
+            game.max_players = max;
+        }
+        game.max_players = game.max_players.max(game.min_players);
+        if let Some(rating) = self.rating {
+            game.rating = Some(rating.clamp(0.0, 1.0));
+        }
+        game.media.overlay(&self.media);
+    }
+
+    /// Takes every field `edit` sets.
+    fn merge(&mut self, edit: &GameMetadata) {
+        self.name = edit.name.clone().or(self.name.take());
+        self.release_date = edit.release_date.or(self.release_date);
+        self.developer = edit.developer.clone().or(self.developer.take());
+        self.publisher = edit.publisher.clone().or(self.publisher.take());
+        self.description = edit.description.clone().or(self.description.take());
+        self.min_players = edit.min_players.or(self.min_players);
+        self.max_players = edit.max_players.or(self.max_players);
+        self.rating = edit.rating.or(self.rating);
+        if !edit.genres.is_empty() {
+            self.genres = edit.genres.clone();
+        }
+        self.media.overlay(&edit.media);
+    }
+}
+
+impl MetadataOverrides {
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let Ok(xml) = fs::read_to_string(path.as_ref()) else {
+            return Self::default();
+        };
+
+        match quick_xml::de::from_str(&xml) {
+            Ok(overrides) => overrides,
+            Err(e) => {
+                println!("⚠️ OSIRIS: METADATA FILE PARSE ERROR: {}. IGNORING IT.", e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) {
+        let result = quick_xml::se::to_string_with_root("metadata", self)
+            .map_err(|e| e.to_string())
+            .and_then(|xml| fs::write(path.as_ref(), xml).map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            println!("⚠️ OSIRIS: COULD NOT SAVE METADATA: {}", e);
+        }
+    }
+
+    pub fn apply(&self, library: &mut RomLibrary) {
+        for saved in &self.system {
+            let Some(system) = library.systems.iter_mut().find(|s| s.name == saved.name) else {
+                continue;
+            };
+            for saved_game in &saved.game {
+                if let Some(game) = system.games.iter_mut().find(|g| g.id == saved_game.id) {
+                    saved_game.apply(game);
+                }
+            }
+        }
+    }
+
+    /// Records `edit` for a game, on top of its earlier edits.
+    pub fn update(&mut self, system_name: &str, game_id: &str, edit: &GameMetadata) {
+        let system = match self.system.iter().position(|s| s.name == system_name) {
+            Some(i) => &mut self.system[i],
+            None => {
+                self.system.push(SystemMetadata {
+                    name: system_name.to_string(),
+                    game: Vec::new(),
+                });
+                self.system.last_mut().unwrap()
+            }
+        };
+        let game = match system.game.iter().position(|g| g.id == game_id) {
+            Some(i) => &mut system.game[i],
+            None => {
+                system.game.push(GameMetadata {
+                    id: game_id.to_string(),
+                    ..GameMetadata::default()
+                });
+                system.game.last_mut().unwrap()
+            }
+        };
+        game.merge(edit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MediaKind;
+    use crate::ui::golden::fixture_roms;
+    use std::path::PathBuf;
+
+    #[test]
+    fn edits_survive_a_save_and_rescan() {
+        let path = std::env::temp_dir().join(format!("osiris-metadata-{}.xml", std::process::id()));
+        let mut overrides = MetadataOverrides::default();
+        overrides.update(
+            "NES",
+            "contra",
+            &GameMetadata {
+                name: Some("Probotector (Europe)".into()),
+                release_date: "1990-12".parse().ok(),
+                ..GameMetadata::default()
+            },
+        );
+        let mut media = MediaPaths::default();
+        media.set(MediaKind::Snap, PathBuf::from("images/contra-snap.png"));
+        overrides.update(
+            "NES",
+            "contra",
+            &GameMetadata {
+                genres: vec!["Run and gun".into()],
+                media,
+                ..GameMetadata::default()
+            },
+        );
+        overrides.save(&path);
+
+        let mut library = fixture_roms();
+        MetadataOverrides::load(&path).apply(&mut library);
+        std::fs::remove_file(&path).unwrap();
+
+        let contra = &library.systems[1].games[2];
+        assert_eq!(contra.name, "Probotector (Europe)");
+        assert_eq!(contra.title, "Probotector");
+        assert_eq!(contra.regions, ["Europe"]);
+        assert_eq!(contra.release_date.unwrap().to_string(), "1990-12");
+        assert_eq!(contra.developer.as_deref(), Some("Konami"));
+        assert_eq!(contra.genres, ["Run and gun"]);
+        assert!(contra.media.get(MediaKind::Snap).is_some());
+    }
+}
@@ -1,18 +1,624 @@
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// The library as shared by the widgets of the UI thread.
+pub type SharedLibrary = Rc<RefCell<RomLibrary>>;
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Game {
     pub id: String,   // short name (e.g., "atetris")
     pub name: String, // long description (e.g., "Tetris (set 1)")
-    pub path: std::path::PathBuf,
-    pub year: String,
-    pub manufacturer: String,
-    pub players: String,
+    pub path: PathBuf,
+    pub title: String,        // name without tags (e.g., "Tetris")
+    pub regions: Vec<String>, // parsed region tags (e.g., ["USA", "Europe"])
+    pub revision: u32,        // 0 for the original release, 1 for "Rev 1"/"Rev A"...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clone_of: Option<String>, // DAT parent id (MAME "cloneof")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<ReleaseDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub developer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+    pub genres: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history: Option<String>, // Arcade History entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mame_info: Option<String>, // mameinfo.dat driver notes
+    pub min_players: u8,
+    pub max_players: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simultaneous_players: Option<u8>, // the rest of max_players take turns
+    pub mature: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<f32>, // 0.0 - 1.0
+    pub media: MediaPaths,
+    pub play_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_played: Option<u64>, // seconds since the UNIX epoch
+    pub favorite: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<EmulationStatus>, // MAME driver status
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub controls: Vec<Control>, // MAME <control>s, every player's
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub button_labels: Vec<String>, // player 1's, from controls.dat
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmulationStatus {
+    Good,
+    Imperfect,
+    Preliminary,
+}
+
+impl EmulationStatus {
+    /// MAME treats "imperfect" drivers as working.
+    pub fn is_working(self) -> bool {
+        self != EmulationStatus::Preliminary
+    }
+}
+
+/// One input device of a game's control panel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Control {
+    pub kind: ControlKind,
+    pub player: u8, // 1 for the first player
+    pub buttons: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ways: Option<u8>, // joystick directions: 2, 4 or 8
+}
+
+impl Default for Control {
+    fn default() -> Self {
+        Self {
+            kind: ControlKind::Buttons,
+            player: 1,
+            buttons: 0,
+            ways: None,
+        }
+    }
+}
+
+impl Control {
+    /// "8-WAY JOYSTICK", "TRACKBALL"...
+    pub fn label(&self) -> String {
+        match (self.kind, self.ways) {
+            (ControlKind::Joystick | ControlKind::DoubleJoystick, Some(ways)) => {
+                format!("{}-WAY {}", ways, self.kind.label())
+            }
+            _ => self.kind.label().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlKind {
+    Joystick,
+    DoubleJoystick,
+    Trackball,
+    Spinner,
+    Paddle,
+    Pedal,
+    Lightgun,
+    Positional,
+    Mouse,
+    Keypad,
+    Keyboard,
+    Buttons,
+}
+
+impl ControlKind {
+    /// The kind of a MAME `<control type>`.
+    pub fn from_mame(control_type: &str) -> Option<Self> {
+        Some(match control_type {
+            "joy" | "stick" => ControlKind::Joystick,
+            "doublejoy" | "triplejoy" => ControlKind::DoubleJoystick,
+            "trackball" => ControlKind::Trackball,
+            "dial" => ControlKind::Spinner,
+            "paddle" => ControlKind::Paddle,
+            "pedal" => ControlKind::Pedal,
+            "lightgun" => ControlKind::Lightgun,
+            "positional" => ControlKind::Positional,
+            "mouse" => ControlKind::Mouse,
+            "keypad" | "mahjong" | "hanafuda" | "gambling" => ControlKind::Keypad,
+            "keyboard" => ControlKind::Keyboard,
+            "only_buttons" => ControlKind::Buttons,
+            _ => return None,
+        })
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ControlKind::Joystick => "JOYSTICK",
+            ControlKind::DoubleJoystick => "DUAL JOYSTICK",
+            ControlKind::Trackball => "TRACKBALL",
+            ControlKind::Spinner => "SPINNER",
+            ControlKind::Paddle => "PADDLE",
+            ControlKind::Pedal => "PEDAL",
+            ControlKind::Lightgun => "LIGHTGUN",
+            ControlKind::Positional => "POSITIONAL",
+            ControlKind::Mouse => "MOUSE",
+            ControlKind::Keypad => "KEYPAD",
+            ControlKind::Keyboard => "KEYBOARD",
+            ControlKind::Buttons => "BUTTONS",
+        }
+    }
+}
+
+/// The controls of the cabinet, from the `<cabinet>` section of the config.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Cabinet {
+    pub players: u8,
+    /// Directions of the joysticks, 0 without any. An 8-way stick also
+    /// plays 4 and 2-way games.
+    pub joystick_ways: u8,
+    /// Two sticks per player, for games like Robotron.
+    pub dual_joysticks: bool,
+    /// Buttons per player.
+    pub buttons: u8,
+    /// Other devices: trackball, spinner, lightgun...
+    #[serde(rename = "device")]
+    pub devices: Vec<Device>,
+    /// What the game list does with games the cabinet can't play.
+    pub incompatible: Incompatible,
+}
+
+/// `<device>trackball</device>`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Device {
+    #[serde(rename = "$text")]
+    pub kind: ControlKind,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Incompatible {
+    #[default]
+    Dim,
+    Hide,
+}
+
+impl Default for Cabinet {
+    fn default() -> Self {
+        Self {
+            players: 2,
+            joystick_ways: 8,
+            dual_joysticks: false,
+            buttons: 6,
+            devices: Vec::new(),
+            incompatible: Incompatible::default(),
+        }
+    }
+}
+
+impl Cabinet {
+    /// Whether the cabinet has every control the game needs for as many
+    /// players as it seats. Games without known controls are assumed to be
+    /// playable.
+    pub fn can_play(&self, game: &Game) -> bool {
+        game.min_players <= self.players
+            && game
+                .controls
+                .iter()
+                .filter(|c| c.player <= self.players)
+                .all(|c| self.has(c))
+    }
+
+    /// Whether the game is to be left out of the lists.
+    pub fn hides(&self, game: &Game) -> bool {
+        self.incompatible == Incompatible::Hide && !self.can_play(game)
+    }
+
+    fn has(&self, control: &Control) -> bool {
+        let stick = control.ways.is_none_or(|ways| ways <= self.joystick_ways);
+        control.buttons <= self.buttons
+            && match control.kind {
+                ControlKind::Buttons => true,
+                ControlKind::Joystick => self.joystick_ways > 0 && stick,
+                ControlKind::DoubleJoystick => {
+                    self.dual_joysticks && self.joystick_ways > 0 && stick
+                }
+                // A spinner stands in for a paddle
+                ControlKind::Paddle => self
+                    .devices
+                    .iter()
+                    .any(|d| matches!(d.kind, ControlKind::Paddle | ControlKind::Spinner)),
+                kind => self.devices.iter().any(|d| d.kind == kind),
+            }
+    }
+}
+
+impl Game {
+    pub fn new(id: String, name: String, path: PathBuf) -> Self {
+        Self {
+            id,
+            name,
+            path,
+            min_players: 1,
+            max_players: 1,
+            ..Default::default()
+        }
+    }
+
+    /// "1", "1-2", "1-4"..., followed by how they play when known: "1-2 ALT"
+    /// (taking turns), "1-4 SIM" (at once) or "1-4 ALT/2 SIM".
+    pub fn players_label(&self) -> String {
+        let range = if self.min_players == self.max_players {
+            self.max_players.to_string()
+        } else {
+            format!("{}-{}", self.min_players, self.max_players)
+        };
+        match self.simultaneous_players {
+            _ if self.max_players <= 1 => range,
+            Some(sim) if sim >= self.max_players => format!("{} SIM", range),
+            Some(sim) if sim <= 1 => format!("{} ALT", range),
+            Some(sim) => format!("{} ALT/{} SIM", range, sim),
+            None => range,
+        }
+    }
+
+    /// Fuzzy match of `query` against the name and id. `None` if neither
+    /// contains the query's characters in order; higher scores match better.
+    pub fn search_score(&self, query: &str) -> Option<u32> {
+        fuzzy_score(query, &self.name).max(fuzzy_score(query, &self.id))
+    }
+
+    /// Position of the game's best region in the priority list. Games without
+    /// a listed region rank after all of them.
+    pub fn region_rank(&self, region_priority: &[String]) -> usize {
+        self.regions
+            .iter()
+            .filter_map(|r| {
+                region_priority
+                    .iter()
+                    .position(|p| p.eq_ignore_ascii_case(r))
+            })
+            .min()
+            .unwrap_or(region_priority.len())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MediaPaths {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub box_front: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub box_back: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marquee: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_screen: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snap: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cartridge: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manual: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    BoxFront,
+    BoxBack,
+    Marquee,
+    TitleScreen,
+    Snap,
+    Cartridge,
+    Manual,
+    Video,
+}
+
+impl MediaKind {
+    pub const ALL: [MediaKind; 8] = [
+        MediaKind::BoxFront,
+        MediaKind::BoxBack,
+        MediaKind::Marquee,
+        MediaKind::TitleScreen,
+        MediaKind::Snap,
+        MediaKind::Cartridge,
+        MediaKind::Manual,
+        MediaKind::Video,
+    ];
+
+    /// Still image kinds, in the order the details panel shows them.
+    pub const IMAGES: [MediaKind; 7] = [
+        MediaKind::BoxFront,
+        MediaKind::Snap,
+        MediaKind::TitleScreen,
+        MediaKind::Marquee,
+        MediaKind::BoxBack,
+        MediaKind::Cartridge,
+        MediaKind::Manual,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MediaKind::BoxFront => "BOX FRONT",
+            MediaKind::BoxBack => "BOX BACK",
+            MediaKind::Marquee => "MARQUEE",
+            MediaKind::TitleScreen => "TITLE SCREEN",
+            MediaKind::Snap => "IN-GAME",
+            MediaKind::Cartridge => "CARTRIDGE",
+            MediaKind::Manual => "MANUAL",
+            MediaKind::Video => "VIDEO",
+        }
+    }
+}
+
+impl MediaPaths {
+    pub fn get(&self, kind: MediaKind) -> Option<&PathBuf> {
+        match kind {
+            MediaKind::BoxFront => self.box_front.as_ref(),
+            MediaKind::BoxBack => self.box_back.as_ref(),
+            MediaKind::Marquee => self.marquee.as_ref(),
+            MediaKind::TitleScreen => self.title_screen.as_ref(),
+            MediaKind::Snap => self.snap.as_ref(),
+            MediaKind::Cartridge => self.cartridge.as_ref(),
+            MediaKind::Manual => self.manual.as_ref(),
+            MediaKind::Video => self.video.as_ref(),
+        }
+    }
+
+    pub fn set(&mut self, kind: MediaKind, path: PathBuf) {
+        let slot = match kind {
+            MediaKind::BoxFront => &mut self.box_front,
+            MediaKind::BoxBack => &mut self.box_back,
+            MediaKind::Marquee => &mut self.marquee,
+            MediaKind::TitleScreen => &mut self.title_screen,
+            MediaKind::Snap => &mut self.snap,
+            MediaKind::Cartridge => &mut self.cartridge,
+            MediaKind::Manual => &mut self.manual,
+            MediaKind::Video => &mut self.video,
+        };
+        *slot = Some(path);
+    }
+
+    /// Takes every path `other` has, keeping ours for the rest.
+    pub fn overlay(&mut self, other: &MediaPaths) {
+        for kind in MediaKind::ALL {
+            if let Some(path) = other.get(kind) {
+                self.set(kind, path.clone());
+            }
+        }
+    }
+
+    /// Available still images, i.e. the ones the `image` crate can decode
+    /// (a PDF manual is listed as media but not shown).
+    pub fn images(&self) -> Vec<(MediaKind, &PathBuf)> {
+        MediaKind::IMAGES
+            .into_iter()
+            .filter_map(|kind| self.get(kind).map(|p| (kind, p)))
+            .filter(|(_, p)| image::ImageFormat::from_path(p).is_ok())
+            .collect()
+    }
+
+    /// The image shown first for a game.
+    pub fn primary_image(&self) -> Option<&PathBuf> {
+        self.images().first().map(|&(_, p)| p)
+    }
+}
+
+/// A release date that may only be known down to the year ("1989") or month
+/// ("1989-04"). Serialized as that same ISO-like string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ReleaseDate {
+    pub year: u16,
+    pub month: Option<u8>,
+    pub day: Option<u8>,
+}
+
+impl fmt::Display for ReleaseDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}", self.year)?;
+        if let Some(month) = self.month {
+            write!(f, "-{:02}", month)?;
+            if let Some(day) = self.day {
+                write!(f, "-{:02}", day)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ReleaseDate {
+    type Err = String;
+
+    /// Accepts "1989", "1989-04-15", "19890415" and EmulationStation's
+    /// "19890415T000000". Unknown digits (MAME's "198?") are rejected.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits: String = s
+            .split('T')
+            .next()
+            .unwrap_or("")
+            .chars()
+            .filter(|c| *c != '-')
+            .collect();
+
+        let field = |range: std::ops::Range<usize>| -> Result<Option<u16>, String> {
+            match digits.get(range) {
+                None | Some("") => Ok(None),
+                Some(part) => part
+                    .parse::<u16>()
+                    .map(Some)
+                    .map_err(|_| format!("invalid release date: {}", s)),
+            }
+        };
+
+        let year = field(0..4)?.ok_or_else(|| format!("invalid release date: {}", s))?;
+        let month = field(4..6)?
+            .map(|m| m as u8)
+            .filter(|m| (1..=12).contains(m));
+        let day = field(6..8)?
+            .map(|d| d as u8)
+            .filter(|d| (1..=31).contains(d));
+
+        Ok(Self {
+            year,
+            month,
+            day: month.and(day),
+        })
+    }
+}
+
+impl TryFrom<String> for ReleaseDate {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<ReleaseDate> for String {
+    fn from(date: ReleaseDate) -> Self {
+        date.to_string()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct System {
     pub name: String,
     pub games: Vec<Game>,
+    pub sort_mode: SortMode,
+    pub filter: GameFilter,
+}
+
+/// Which games of a system the list shows. The default shows everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decade: Option<u16>, // 1980 for the 80s
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub players: Option<u8>, // playable by at least this many players
+    pub favorites_only: bool,
+    pub working_only: bool,
+    pub hide_mature: bool,
+}
+
+impl GameFilter {
+    pub fn is_active(&self) -> bool {
+        *self != GameFilter::default()
+    }
+
+    pub fn matches(&self, game: &Game) -> bool {
+        if let Some(genre) = &self.genre
+            && !game.genres.iter().any(|g| g.eq_ignore_ascii_case(genre))
+        {
+            return false;
+        }
+        if let Some(decade) = self.decade
+            && game.release_date.map(|d| d.year / 10 * 10) != Some(decade)
+        {
+            return false;
+        }
+        if let Some(players) = self.players
+            && game.max_players < players
+        {
+            return false;
+        }
+        if self.favorites_only && !game.favorite {
+            return false;
+        }
+        // Unknown status counts as working
+        if self.working_only && !game.status.is_none_or(EmulationStatus::is_working) {
+            return false;
+        }
+        if self.hide_mature && game.mature {
+            return false;
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortMode {
+    #[default]
+    Name,
+    Year,
+    Manufacturer,
+    Players,
+    PlayCount,
+    LastPlayed,
+    Rating,
+}
+
+impl SortMode {
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Name => SortMode::Year,
+            SortMode::Year => SortMode::Manufacturer,
+            SortMode::Manufacturer => SortMode::Players,
+            SortMode::Players => SortMode::PlayCount,
+            SortMode::PlayCount => SortMode::LastPlayed,
+            SortMode::LastPlayed => SortMode::Rating,
+            SortMode::Rating => SortMode::Name,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Name => "NAME",
+            SortMode::Year => "YEAR",
+            SortMode::Manufacturer => "MANUFACTURER",
+            SortMode::Players => "PLAYERS",
+            SortMode::PlayCount => "PLAY COUNT",
+            SortMode::LastPlayed => "LAST PLAYED",
+            SortMode::Rating => "RATING",
+        }
+    }
+
+    /// Ordering of two games under this mode. Unknown values go last and ties
+    /// fall back to the name.
+    pub fn compare(self, a: &Game, b: &Game) -> Ordering {
+        fn known_first<T: Ord>(a: &Option<T>, b: &Option<T>) -> Ordering {
+            match (a, b) {
+                (Some(a), Some(b)) => a.cmp(b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+
+        let by_mode = match self {
+            SortMode::Name => Ordering::Equal,
+            SortMode::Year => known_first(&a.release_date, &b.release_date),
+            SortMode::Manufacturer => known_first(
+                &a.developer.as_ref().map(|d| d.to_lowercase()),
+                &b.developer.as_ref().map(|d| d.to_lowercase()),
+            ),
+            SortMode::Players => b.max_players.cmp(&a.max_players),
+            SortMode::PlayCount => b.play_count.cmp(&a.play_count),
+            SortMode::LastPlayed => {
+                known_first(&a.last_played.map(Reverse), &b.last_played.map(Reverse))
+            }
+            SortMode::Rating => known_first(
+                &a.rating.map(|r| Reverse((r * 1000.0) as i32)),
+                &b.rating.map(|r| Reverse((r * 1000.0) as i32)),
+            ),
+        };
+        by_mode.then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    }
 }
 
 pub struct RomLibrary {
@@ -25,4 +631,148 @@ impl RomLibrary {
             systems: Vec::new(),
         }
     }
+
+    /// Indices of the game `game_id` of the system `system_name`.
+    pub fn find_game(&self, system_name: &str, game_id: &str) -> Option<(usize, usize)> {
+        let system_idx = self.systems.iter().position(|s| s.name == system_name)?;
+        let game_idx = self.systems[system_idx]
+            .games
+            .iter()
+            .position(|g| g.id == game_id)?;
+        Some((system_idx, game_idx))
+    }
+}
+
+impl System {
+    /// Groups the regional copies of each title (1G1R). Every group lists
+    /// game indices with the preferred copy first, and groups keep the order
+    /// in which their first copy appears in `games`.
+    pub fn one_game_one_rom(&self, region_priority: &[String]) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_of: HashMap<String, usize> = HashMap::new();
+        let has_dat_info = self.games.iter().any(|g| g.clone_of.is_some());
+
+        for (idx, game) in self.games.iter().enumerate() {
+            // DAT parent/clone info wins over the parsed title
+            let key = match &game.clone_of {
+                Some(parent) => parent.clone(),
+                None if has_dat_info => game.id.clone(),
+                None => game.title.to_lowercase(),
+            };
+
+            let group = *group_of.entry(key).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group].push(idx);
+        }
+
+        for group in &mut groups {
+            group.sort_by_key(|&idx| {
+                let game = &self.games[idx];
+                (
+                    game.region_rank(region_priority),
+                    game.clone_of.is_some(),
+                    Reverse(game.revision),
+                    idx,
+                )
+            });
+        }
+        groups
+    }
+}
+
+/// Case-insensitive subsequence match. Consecutive characters and matches at
+/// the start of a word score extra, so "smb" ranks "Super Mario Bros." above
+/// "Sumo Bout".
+fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut prev_match = None;
+
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = (pos..text.len()).find(|&i| text[i] == q)?;
+        score += 1;
+        if found > 0 && prev_match == Some(found - 1) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        prev_match = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::parse_name_tags;
+
+    fn system(names: &[&str]) -> System {
+        let games = names
+            .iter()
+            .map(|name| {
+                let mut game = Game::new(name.to_string(), name.to_string(), PathBuf::new());
+                (game.title, game.regions, game.revision) = parse_name_tags(name);
+                game
+            })
+            .collect();
+        System {
+            name: "NES".into(),
+            games,
+            sort_mode: SortMode::default(),
+            filter: GameFilter::default(),
+        }
+    }
+
+    #[test]
+    fn release_dates_parse() {
+        let date = |year, month, day| ReleaseDate { year, month, day };
+        assert_eq!("1989".parse(), Ok(date(1989, None, None)));
+        assert_eq!("1989-04".parse(), Ok(date(1989, Some(4), None)));
+        assert_eq!("1989-04-15".parse(), Ok(date(1989, Some(4), Some(15))));
+        assert_eq!("19890415T000000".parse(), Ok(date(1989, Some(4), Some(15))));
+        // Out of range months and days are dropped, not the year
+        assert_eq!("1989-13-40".parse(), Ok(date(1989, None, None)));
+    }
+
+    #[test]
+    fn release_dates_with_unknown_digits_are_rejected() {
+        for s in ["198?", "19??", "", "soon", "89", "1989-xx"] {
+            assert!(s.parse::<ReleaseDate>().is_err(), "{:?} parsed", s);
+        }
+    }
+
+    #[test]
+    fn region_rank_follows_the_priority() {
+        let priority = ["USA".to_string(), "Europe".to_string()];
+        let system = system(&["A (Europe)", "A (USA, Europe)", "A (Japan)", "A (Unl)"]);
+        let ranks: Vec<_> = system
+            .games
+            .iter()
+            .map(|g| g.region_rank(&priority))
+            .collect();
+        assert_eq!(ranks, [1, 0, 2, 2]);
+    }
+
+    #[test]
+    fn one_game_one_rom_prefers_region_then_revision() {
+        let system = system(&[
+            "Tetris (Japan)",
+            "Contra (USA)",
+            "Tetris (Europe)",
+            "Tetris (USA)",
+            "Tetris (USA) (Rev 1)",
+            "Tetris (USA, Europe)",
+        ]);
+        let priority = ["USA".to_string(), "Europe".to_string()];
+        // USA ties with USA, Europe: the later revision, then the scan order
+        assert_eq!(
+            system.one_game_one_rom(&priority),
+            [vec![4, 3, 5, 2, 0], vec![1]]
+        );
+    }
 }
@@ -0,0 +1,164 @@
+use crate::commands::ControlCommand;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// One input of a session, as seen by the app: a press, an auto-repeat of
+/// the held navigation, the release of all controls, or a one-off press
+/// (from a control client) that leaves what is held alone.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    Press(ControlCommand),
+    Tap(ControlCommand),
+    Repeat(ControlCommand),
+    Release,
+}
+
+/// Writes a session's inputs to a text file, one per line and flushed
+/// right away so a crash still leaves the steps leading up to it:
+///
+/// ```text
+/// 1520 press down
+/// 1920 repeat down
+/// 2000 release
+/// ```
+///
+/// Times are milliseconds since the recording started.
+pub struct Recorder {
+    out: BufWriter<File>,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "# osiris input recording")?;
+        Ok(Self {
+            out,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, input: &Input) {
+        let at = self.started.elapsed().as_millis();
+        let line = match input {
+            Input::Press(cmd) => format!("{} press {}", at, cmd),
+            Input::Tap(cmd) => format!("{} tap {}", at, cmd),
+            Input::Repeat(cmd) => format!("{} repeat {}", at, cmd),
+            Input::Release => format!("{} release", at),
+        };
+        if let Err(e) = writeln!(self.out, "{}", line).and_then(|_| self.out.flush()) {
+            println!("⚠️ OSIRIS: INPUT RECORDING FAILED: {}.", e);
+        }
+    }
+}
+
+/// Plays a recording back at its original pace. Auto-repeats come from the
+/// recording too, so a replay does not depend on the frame timing.
+pub struct Replay {
+    inputs: VecDeque<(Duration, Input)>,
+    started: Option<Instant>,
+}
+
+impl Replay {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let inputs = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(i, line)| {
+                parse_line(line).map_err(|e| format!("{}:{}: {}", path.display(), i + 1, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            inputs,
+            started: None,
+        })
+    }
+
+    /// The next input due at `now`, if any. The clock starts on the first
+    /// call.
+    pub fn next_due(&mut self, now: Instant) -> Option<Input> {
+        let started = *self.started.get_or_insert(now);
+        let &(at, _) = self.inputs.front()?;
+        if now >= started + at {
+            self.inputs.pop_front().map(|(_, input)| input)
+        } else {
+            None
+        }
+    }
+
+    /// When the next input is due.
+    pub fn next_at(&self) -> Option<Instant> {
+        let (at, _) = self.inputs.front()?;
+        Some(self.started? + *at)
+    }
+
+    /// Every remaining input, without waiting.
+    pub fn drain(&mut self) -> impl Iterator<Item = Input> + '_ {
+        self.inputs.drain(..).map(|(_, input)| input)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.inputs.is_empty()
+    }
+}
+
+fn parse_line(line: &str) -> Result<(Duration, Input), String> {
+    let mut fields = line.split_whitespace();
+    let at: u64 = fields
+        .next()
+        .and_then(|at| at.parse().ok())
+        .ok_or("expected a time in milliseconds")?;
+    let input = match (fields.next(), fields.next()) {
+        (Some("press"), Some(cmd)) => Input::Press(cmd.parse()?),
+        (Some("tap"), Some(cmd)) => Input::Tap(cmd.parse()?),
+        (Some("repeat"), Some(cmd)) => Input::Repeat(cmd.parse()?),
+        (Some("release"), None) => Input::Release,
+        _ => return Err(format!("invalid input {:?}", line.trim())),
+    };
+    Ok((Duration::from_millis(at), input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{ActionCommand, NavigationCommand};
+
+    #[test]
+    fn recording_plays_back_in_order() {
+        let path = std::env::temp_dir().join(format!("osiris-replay-{}.txt", std::process::id()));
+        let inputs = [
+            Input::Press(ControlCommand::Navigation(NavigationCommand::Down)),
+            Input::Repeat(ControlCommand::Navigation(NavigationCommand::Down)),
+            Input::Release,
+            Input::Tap(ControlCommand::Action(ActionCommand::Select)),
+            Input::Press(ControlCommand::Action(ActionCommand::OpenSearch)),
+        ];
+
+        let mut recorder = Recorder::create(&path).unwrap();
+        for input in &inputs {
+            recorder.record(input);
+        }
+        drop(recorder);
+
+        let mut replay = Replay::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replay.drain().collect::<Vec<_>>(), inputs);
+    }
+
+    #[test]
+    fn invalid_lines_are_reported() {
+        assert!(parse_line("12 press sideways").is_err());
+        assert!(parse_line("press down").is_err());
+        assert_eq!(
+            parse_line("40 release"),
+            Ok((Duration::from_millis(40), Input::Release))
+        );
+    }
+}
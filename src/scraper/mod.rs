@@ -0,0 +1,432 @@
+use crate::config::Config;
+use crate::metadata::{GameMetadata, MetadataOverrides};
+use crate::models::{Game, MediaKind, MediaPaths, RomLibrary};
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod screenscraper;
+pub use screenscraper::ScreenScraper;
+
+/// The ROM file a game is looked up by.
+#[derive(Debug, Clone)]
+pub struct RomInfo {
+    pub file_name: String,
+    pub size: u64,
+    /// CRC32 of the file as upper case hex; `None` if it could not be read.
+    pub crc32: Option<String>,
+}
+
+impl RomInfo {
+    pub fn read(path: &Path) -> Self {
+        let file_name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("")
+            .to_string();
+        let size = fs::metadata(path).map_or(0, |m| m.len());
+        let crc32 = checksum(path).ok().map(|crc| format!("{:08X}", crc));
+        Self {
+            file_name,
+            size,
+            crc32,
+        }
+    }
+}
+
+fn checksum(path: &Path) -> io::Result<u32> {
+    let mut file = File::open(path)?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buf)? {
+            0 => return Ok(hasher.finalize()),
+            n => hasher.update(&buf[..n]),
+        }
+    }
+}
+
+/// What a provider knows about a game.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scraped {
+    pub metadata: GameMetadata,
+    /// Where to download the images, by kind.
+    pub images: Vec<(MediaKind, String)>,
+}
+
+/// A source of game metadata, such as ScreenScraper or TheGamesDB.
+pub trait Provider {
+    /// What the provider knows about `rom` of `system` (a ROM directory
+    /// name such as `NES`); `None` if it does not know the game.
+    fn lookup(&mut self, system: &str, rom: &RomInfo) -> Result<Option<Scraped>, String>;
+    fn download(&mut self, url: &str) -> Result<Vec<u8>, String>;
+}
+
+/// Spaces requests out to stay within a provider's quota.
+pub struct RateLimiter {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: None,
+        }
+    }
+
+    /// Sleeps until the next request may be made.
+    pub fn wait(&mut self) {
+        if let Some(next) = self.last.map(|last| last + self.interval) {
+            thread::sleep(next.saturating_duration_since(Instant::now()));
+        }
+        self.last = Some(Instant::now());
+    }
+}
+
+/// Provider answers kept on disk as `<dir>/<system>/<crc>.json`, so a game
+/// is only looked up once. Games the provider does not know are kept as
+/// `null`; delete the file (or the whole directory) to look them up again.
+struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    fn path(&self, system: &str, rom: &RomInfo) -> PathBuf {
+        let key = rom.crc32.clone().unwrap_or_else(|| {
+            rom.file_name
+                .chars()
+                .map(|c| if c.is_alphanumeric() { c } else { '_' })
+                .collect()
+        });
+        self.dir.join(system).join(format!("{}.json", key))
+    }
+
+    fn get(&self, system: &str, rom: &RomInfo) -> Option<Option<Scraped>> {
+        let json = fs::read_to_string(self.path(system, rom)).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    fn put(&self, system: &str, rom: &RomInfo, scraped: &Option<Scraped>) {
+        let path = self.path(system, rom);
+        let result = fs::create_dir_all(path.parent().unwrap_or(&self.dir))
+            .map_err(|e| e.to_string())
+            .and_then(|_| serde_json::to_string_pretty(scraped).map_err(|e| e.to_string()))
+            .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            println!("⚠️ OSIRIS: COULD NOT CACHE {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// How a scraping run went.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub found: usize,
+    pub not_found: usize,
+    pub failed: usize,
+    pub images: usize,
+}
+
+/// `osiris scrape <system>`: looks the games of a system up with the
+/// configured provider and keeps what they were missing in the metadata
+/// file.
+pub fn run(config: &Config, mut library: RomLibrary, system: &str) -> Result<(), String> {
+    let mut overrides = MetadataOverrides::load(&config.metadata_file);
+    overrides.apply(&mut library);
+
+    let mut provider = ScreenScraper::new(&config.scraper);
+    let summary = scrape_system(
+        &mut library,
+        system,
+        &mut provider,
+        &config.scraper.cache_dir,
+        &mut overrides,
+        &config.metadata_file,
+    )?;
+    println!(
+        "✅ OSIRIS: SCRAPE COMPLETE. {} FOUND, {} UNKNOWN, {} FAILED, {} IMAGES DOWNLOADED.",
+        summary.found, summary.not_found, summary.failed, summary.images
+    );
+    Ok(())
+}
+
+/// Looks up every game of `system_name` (cached answers first), fills in
+/// the fields and images each one lacks and records them in `overrides`.
+/// Values the games already have, scanned or edited, are kept.
+pub fn scrape_system(
+    library: &mut RomLibrary,
+    system_name: &str,
+    provider: &mut dyn Provider,
+    cache_dir: &Path,
+    overrides: &mut MetadataOverrides,
+    metadata_file: &Path,
+) -> Result<Summary, String> {
+    let system = library
+        .systems
+        .iter_mut()
+        .find(|s| s.name.eq_ignore_ascii_case(system_name))
+        .ok_or_else(|| format!("no system {:?} in the library", system_name))?;
+    let cache = Cache {
+        dir: cache_dir.to_path_buf(),
+    };
+
+    let mut summary = Summary::default();
+    let total = system.games.len();
+    for (i, game) in system.games.iter_mut().enumerate() {
+        let rom = RomInfo::read(&game.path);
+        let scraped = match cache.get(&system.name, &rom) {
+            Some(cached) => cached,
+            None => match provider.lookup(&system.name, &rom) {
+                Ok(scraped) => {
+                    cache.put(&system.name, &rom, &scraped);
+                    scraped
+                }
+                Err(e) => {
+                    println!(
+                        "OSIRIS: [{}/{}] {} ... ERROR: {}",
+                        i + 1,
+                        total,
+                        game.name,
+                        e
+                    );
+                    summary.failed += 1;
+                    continue;
+                }
+            },
+        };
+        let Some(scraped) = scraped else {
+            println!("OSIRIS: [{}/{}] {} ... NOT FOUND", i + 1, total, game.name);
+            summary.not_found += 1;
+            continue;
+        };
+        summary.found += 1;
+
+        let mut edit = missing_fields(game, &scraped.metadata);
+        let images = download_images(game, &scraped, provider, &mut edit.media);
+        summary.images += images;
+        println!(
+            "OSIRIS: [{}/{}] {} ... FOUND ({} IMAGES)",
+            i + 1,
+            total,
+            game.name,
+            images
+        );
+
+        if edit != GameMetadata::default() {
+            edit.apply(game);
+            overrides.update(&system.name, &game.id, &edit);
+            // Saved as we go, so an interrupted run keeps what it got
+            overrides.save(metadata_file);
+        }
+    }
+    Ok(summary)
+}
+
+/// The fields of `found` that `game` has no value for.
+fn missing_fields(game: &Game, found: &GameMetadata) -> GameMetadata {
+    // Scanned games without player info default to a single player
+    let players_known = game.max_players > 1;
+    GameMetadata {
+        release_date: found.release_date.filter(|_| game.release_date.is_none()),
+        developer: found.developer.clone().filter(|_| game.developer.is_none()),
+        publisher: found.publisher.clone().filter(|_| game.publisher.is_none()),
+        genres: if game.genres.is_empty() {
+            found.genres.clone()
+        } else {
+            Vec::new()
+        },
+        description: found
+            .description
+            .clone()
+            .filter(|_| game.description.is_none()),
+        min_players: found.min_players.filter(|_| !players_known),
+        max_players: found.max_players.filter(|_| !players_known),
+        rating: found.rating.filter(|_| game.rating.is_none()),
+        ..GameMetadata::default()
+    }
+}
+
+/// Downloads the images `game` has none of yet into its system's `images`
+/// directory and adds them to `media`. Returns how many were stored.
+fn download_images(
+    game: &Game,
+    scraped: &Scraped,
+    provider: &mut dyn Provider,
+    media: &mut MediaPaths,
+) -> usize {
+    let system_dir = game.path.parent().unwrap_or(Path::new("."));
+    let mut stored = 0;
+    for (kind, url) in &scraped.images {
+        if game.media.get(*kind).is_some() || media.get(*kind).is_some() {
+            continue;
+        }
+        let result = provider
+            .download(url)
+            .and_then(|data| storage::save_media(system_dir, &game.id, *kind, &data));
+        match result {
+            Ok(path) => {
+                media.set(*kind, path);
+                stored += 1;
+            }
+            Err(e) => println!(
+                "⚠️ OSIRIS: {} {} DOWNLOAD FAILED: {}",
+                game.id,
+                kind.label(),
+                e
+            ),
+        }
+    }
+    stored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScraperConfig;
+    use serde_json::json;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A ScreenScraper stand-in that knows one ROM (by its CRC) and serves
+    /// a box image. Returns its base URL and a count of the lookups.
+    fn mock_provider(known_crc: String) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let lookups = Arc::new(AtomicUsize::new(0));
+
+        let game = json!({ "response": { "jeu": {
+            "noms": [{ "region": "wor", "text": "Contra" }],
+            "synopsis": [
+                { "langue": "fr", "text": "Deux commandos..." },
+                { "langue": "en", "text": "Two commandos..." },
+            ],
+            "dates": [{ "region": "jp", "text": "1988-02-09" }, { "region": "us", "text": "1988-02" }],
+            "developpeur": { "text": "Konami" },
+            "editeur": { "text": "Konami" },
+            "joueurs": { "text": "1-2" },
+            "note": { "text": "16" },
+            "genres": [{ "noms": [{ "langue": "en", "text": "Shooter" }] }],
+            "medias": [
+                { "type": "box-2D", "region": "jp", "url": format!("{}/media/box-jp.png", base_url) },
+                { "type": "box-2D", "region": "us", "url": format!("{}/media/box-us.png", base_url) },
+            ],
+        }}});
+        let image = tiny_skia::Pixmap::new(2, 2).unwrap().encode_png().unwrap();
+
+        let count = lookups.clone();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 2 {
+                    header.clear();
+                }
+
+                let (status, body) = if request.contains("/jeuInfos.php") {
+                    count.fetch_add(1, Ordering::SeqCst);
+                    if request.contains(&format!("crc={}", known_crc)) {
+                        ("200 OK", game.to_string().into_bytes())
+                    } else {
+                        ("404 Not Found", b"Erreur : Jeu non trouvee !".to_vec())
+                    }
+                } else if request.contains("/media/box-us.png") {
+                    ("200 OK", image.clone())
+                } else {
+                    ("404 Not Found", Vec::new())
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+        (base_url, lookups)
+    }
+
+    #[test]
+    fn missing_metadata_and_images_are_filled_in() {
+        let dir = std::env::temp_dir().join(format!("osiris-scrape-{}", std::process::id()));
+        let nes = dir.join("nes");
+        fs::create_dir_all(&nes).unwrap();
+        fs::write(nes.join("contra.nes"), "CONTRA").unwrap();
+        fs::write(nes.join("zelda.nes"), "ZELDA").unwrap();
+        let crc = format!("{:08X}", crc32fast::hash(b"CONTRA"));
+        let (base_url, lookups) = mock_provider(crc);
+
+        let mut library = storage::scan_roms(&dir);
+        let contra = library.find_game("NES", "contra").unwrap();
+        library.systems[contra.0].games[contra.1].publisher = Some("Ultra Games".into());
+
+        let config = ScraperConfig {
+            base_url,
+            interval_ms: 0,
+            ..ScraperConfig::default()
+        };
+        let cache_dir = dir.join("cache");
+        let metadata_file = dir.join("metadata.xml");
+        let mut overrides = MetadataOverrides::default();
+        let mut scrape = |library: &mut RomLibrary| {
+            let mut provider = ScreenScraper::new(&config);
+            scrape_system(
+                library,
+                "nes",
+                &mut provider,
+                &cache_dir,
+                &mut overrides,
+                &metadata_file,
+            )
+            .unwrap()
+        };
+
+        let summary = scrape(&mut library);
+        assert_eq!(
+            summary,
+            Summary {
+                found: 1,
+                not_found: 1,
+                failed: 0,
+                images: 1
+            }
+        );
+        let game = &library.systems[contra.0].games[contra.1];
+        assert_eq!(game.developer.as_deref(), Some("Konami"));
+        assert_eq!(game.publisher.as_deref(), Some("Ultra Games"));
+        assert_eq!(game.release_date.unwrap().to_string(), "1988-02");
+        assert_eq!(game.description.as_deref(), Some("Two commandos..."));
+        assert_eq!(game.genres, ["Shooter"]);
+        assert_eq!(game.players_label(), "1-2");
+        assert_eq!(game.rating, Some(0.8));
+        let box_front = game.media.get(MediaKind::BoxFront).unwrap();
+        assert!(box_front.ends_with("images/contra-boxfront.png"));
+        assert!(box_front.exists());
+
+        // The edits are kept, and a second run is answered from the cache
+        let mut rescanned = storage::scan_roms(&dir);
+        MetadataOverrides::load(&metadata_file).apply(&mut rescanned);
+        assert_eq!(
+            rescanned.systems[contra.0].games[contra.1]
+                .developer
+                .as_deref(),
+            Some("Konami")
+        );
+        assert_eq!(scrape(&mut rescanned).found, 1);
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
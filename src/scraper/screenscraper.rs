@@ -0,0 +1,213 @@
+use super::{Provider, RateLimiter, RomInfo, Scraped};
+use crate::config::ScraperConfig;
+use crate::metadata::GameMetadata;
+use crate::models::MediaKind;
+use serde_json::Value;
+use std::io::Read;
+use std::time::Duration;
+
+/// Largest image downloaded.
+const MAX_IMAGE_SIZE: u64 = 16 * 1024 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Region codes, most wanted first, for names, dates and box art.
+const REGIONS: [&str; 5] = ["wor", "us", "eu", "ss", "jp"];
+
+/// ScreenScraper's media types, in the order they are downloaded.
+const MEDIA_TYPES: [(&str, MediaKind); 6] = [
+    ("box-2D", MediaKind::BoxFront),
+    ("ss", MediaKind::Snap),
+    ("sstitle", MediaKind::TitleScreen),
+    ("wheel", MediaKind::Marquee),
+    ("box-2D-back", MediaKind::BoxBack),
+    ("support-2D", MediaKind::Cartridge),
+];
+
+/// ScreenScraper's ids of the systems, by common ROM directory names.
+const SYSTEM_IDS: [(&str, u32); 30] = [
+    ("MEGADRIVE", 1),
+    ("GENESIS", 1),
+    ("MASTERSYSTEM", 2),
+    ("NES", 3),
+    ("FAMICOM", 3),
+    ("SNES", 4),
+    ("SUPERFAMICOM", 4),
+    ("GB", 9),
+    ("GAMEBOY", 9),
+    ("GBC", 10),
+    ("GBA", 12),
+    ("N64", 14),
+    ("NDS", 15),
+    ("SEGA32X", 19),
+    ("SEGACD", 20),
+    ("GAMEGEAR", 21),
+    ("SATURN", 22),
+    ("DREAMCAST", 23),
+    ("ATARI2600", 26),
+    ("ATARI7800", 41),
+    ("LYNX", 28),
+    ("PCENGINE", 31),
+    ("TG16", 31),
+    ("PSX", 57),
+    ("PS1", 57),
+    ("MAME", 75),
+    ("ARCADE", 75),
+    ("FBNEO", 75),
+    ("NEOGEO", 142),
+    ("NGP", 25),
+];
+
+/// The ScreenScraper `jeuInfos` API (or anything answering like it, such as
+/// a mock server in tests).
+pub struct ScreenScraper {
+    agent: ureq::Agent,
+    config: ScraperConfig,
+    limiter: RateLimiter,
+}
+
+impl ScreenScraper {
+    pub fn new(config: &ScraperConfig) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout(TIMEOUT)
+                .user_agent(concat!("osiris/", env!("CARGO_PKG_VERSION")))
+                .build(),
+            config: config.clone(),
+            limiter: RateLimiter::new(Duration::from_millis(config.interval_ms)),
+        }
+    }
+}
+
+impl Provider for ScreenScraper {
+    fn lookup(&mut self, system: &str, rom: &RomInfo) -> Result<Option<Scraped>, String> {
+        let url = format!(
+            "{}/jeuInfos.php",
+            self.config.base_url.trim_end_matches('/')
+        );
+        let mut request = self
+            .agent
+            .get(&url)
+            .query("devid", &self.config.dev_id)
+            .query("devpassword", &self.config.dev_password)
+            .query("softname", "osiris")
+            .query("output", "json")
+            .query("romtype", "rom")
+            .query("romnom", &rom.file_name)
+            .query("romtaille", &rom.size.to_string());
+        if let Some(crc) = &rom.crc32 {
+            request = request.query("crc", crc);
+        }
+        if let Some(&(_, id)) = SYSTEM_IDS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(system))
+        {
+            request = request.query("systemeid", &id.to_string());
+        }
+        if let (Some(user), Some(password)) = (&self.config.user, &self.config.password) {
+            request = request.query("ssid", user).query("sspassword", password);
+        }
+
+        self.limiter.wait();
+        match request.call() {
+            Ok(response) => {
+                let body: Value = response.into_json().map_err(|e| e.to_string())?;
+                let game = &body["response"]["jeu"];
+                Ok((!game.is_null()).then(|| parse_game(game, &self.config.language)))
+            }
+            // "Erreur : Jeu non trouvée !"
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(describe(e)),
+        }
+    }
+
+    fn download(&mut self, url: &str) -> Result<Vec<u8>, String> {
+        self.limiter.wait();
+        let response = self.agent.get(url).call().map_err(describe)?;
+        let mut data = Vec::new();
+        response
+            .into_reader()
+            .take(MAX_IMAGE_SIZE)
+            .read_to_end(&mut data)
+            .map_err(|e| e.to_string())?;
+        Ok(data)
+    }
+}
+
+/// The error without the URL, which carries the account's credentials.
+fn describe(error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(status, response) => format!(
+            "HTTP {}: {}",
+            status,
+            response.into_string().unwrap_or_default().trim()
+        ),
+        ureq::Error::Transport(transport) => match transport.message() {
+            Some(message) => format!("{}: {}", transport.kind(), message),
+            None => transport.kind().to_string(),
+        },
+    }
+}
+
+fn parse_game(game: &Value, language: &str) -> Scraped {
+    let languages = [language, "en"];
+    let text = |value: &Value| value["text"].as_str().map(str::to_string);
+
+    let players: Vec<u8> = game["joueurs"]["text"]
+        .as_str()
+        .unwrap_or("")
+        .split('-')
+        .filter_map(|n| n.trim().parse().ok())
+        .collect();
+    let genres = game["genres"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|genre| pick(&genre["noms"], "langue", &languages))
+        .filter_map(text)
+        .collect();
+
+    let metadata = GameMetadata {
+        name: pick(&game["noms"], "region", &REGIONS).and_then(text),
+        release_date: pick(&game["dates"], "region", &REGIONS)
+            .and_then(text)
+            .and_then(|date| date.parse().ok()),
+        developer: text(&game["developpeur"]),
+        publisher: text(&game["editeur"]),
+        genres,
+        description: pick(&game["synopsis"], "langue", &languages).and_then(text),
+        min_players: players.first().copied(),
+        max_players: players.last().copied(),
+        // Out of 20
+        rating: text(&game["note"])
+            .and_then(|note| note.parse::<f32>().ok())
+            .map(|note| note / 20.0),
+        ..GameMetadata::default()
+    };
+
+    let medias = game["medias"].as_array().cloned().unwrap_or_default();
+    let images = MEDIA_TYPES
+        .iter()
+        .filter_map(|&(media_type, kind)| {
+            let of_type = Value::Array(
+                medias
+                    .iter()
+                    .filter(|m| m["type"] == media_type)
+                    .cloned()
+                    .collect(),
+            );
+            let url = pick(&of_type, "region", &REGIONS)?["url"].as_str()?;
+            Some((kind, url.to_string()))
+        })
+        .collect();
+
+    Scraped { metadata, images }
+}
+
+/// The entry of a ScreenScraper list whose `key` (region or language) comes
+/// first in `wanted`, or the first entry if none matches.
+fn pick<'a>(list: &'a Value, key: &str, wanted: &[&str]) -> Option<&'a Value> {
+    let entries = list.as_array()?;
+    wanted
+        .iter()
+        .find_map(|w| entries.iter().find(|e| e[key] == *w))
+        .or(entries.first())
+}
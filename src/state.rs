@@ -0,0 +1,110 @@
+use crate::models::{GameFilter, RomLibrary, SortMode};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Everything the user changes at runtime (view preferences, play history),
+/// kept in the state file between sessions and keyed by system and game id so
+/// it survives rescans.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserState {
+    pub system: Vec<SystemState>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SystemState {
+    #[serde(rename = "@name")]
+    pub name: String,
+    pub sort: SortMode,
+    pub filter: GameFilter,
+    pub game: Vec<GameState>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameState {
+    #[serde(rename = "@id")]
+    pub id: String,
+    pub play_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_played: Option<u64>,
+    pub favorite: bool,
+}
+
+impl GameState {
+    fn is_empty(&self) -> bool {
+        self.play_count == 0 && self.last_played.is_none() && !self.favorite
+    }
+}
+
+impl UserState {
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let Ok(xml) = fs::read_to_string(path.as_ref()) else {
+            return Self::default();
+        };
+
+        match quick_xml::de::from_str(&xml) {
+            Ok(state) => state,
+            Err(e) => {
+                println!("⚠️ OSIRIS: STATE FILE PARSE ERROR: {}. STARTING FRESH.", e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) {
+        let result = quick_xml::se::to_string_with_root("state", self)
+            .map_err(|e| e.to_string())
+            .and_then(|xml| fs::write(path.as_ref(), xml).map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            println!("⚠️ OSIRIS: COULD NOT SAVE STATE: {}", e);
+        }
+    }
+
+    /// Snapshot of the user data currently held by the library.
+    pub fn capture(library: &RomLibrary) -> Self {
+        let system = library
+            .systems
+            .iter()
+            .map(|s| SystemState {
+                name: s.name.clone(),
+                sort: s.sort_mode,
+                filter: s.filter.clone(),
+                game: s
+                    .games
+                    .iter()
+                    .map(|g| GameState {
+                        id: g.id.clone(),
+                        play_count: g.play_count,
+                        last_played: g.last_played,
+                        favorite: g.favorite,
+                    })
+                    .filter(|g| !g.is_empty())
+                    .collect(),
+            })
+            .collect();
+
+        Self { system }
+    }
+
+    pub fn apply(&self, library: &mut RomLibrary) {
+        for saved in &self.system {
+            let Some(system) = library.systems.iter_mut().find(|s| s.name == saved.name) else {
+                continue;
+            };
+            system.sort_mode = saved.sort;
+            system.filter = saved.filter.clone();
+
+            for saved_game in &saved.game {
+                if let Some(game) = system.games.iter_mut().find(|g| g.id == saved_game.id) {
+                    game.play_count = saved_game.play_count;
+                    game.last_played = saved_game.last_played;
+                    game.favorite = saved_game.favorite;
+                }
+            }
+        }
+    }
+}
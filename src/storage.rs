@@ -1,10 +1,24 @@
-use crate::models::{Game, RomLibrary, System};
+use crate::config::MameConfig;
+use crate::dats;
+use crate::models::{
+    Control, ControlKind, EmulationStatus, Game, GameFilter, MediaKind, MediaPaths, RomLibrary,
+    SortMode, System,
+};
 use quick_xml::events::Event;
 use quick_xml::reader::Reader;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+/// Scans the ROMs and adds what the MAME support files know about them.
+pub fn load_library<P: AsRef<Path>>(roms_dir: P, mame: &MameConfig) -> RomLibrary {
+    let mut library = scan_roms(roms_dir);
+    dats::apply(&mut library, mame);
+    library
+}
+
 pub fn scan_roms<P: AsRef<Path>>(root_path: P) -> RomLibrary {
     let mut library = RomLibrary::new();
     let root = root_path.as_ref();
@@ -32,6 +46,8 @@ pub fn scan_roms<P: AsRef<Path>>(root_path: P) -> RomLibrary {
                 library.systems.push(System {
                     name: system_name,
                     games,
+                    sort_mode: SortMode::default(),
+                    filter: GameFilter::default(),
                 });
             }
         }
@@ -43,19 +59,17 @@ pub fn scan_roms<P: AsRef<Path>>(root_path: P) -> RomLibrary {
 
 fn scan_generic_dir(path: &Path) -> Vec<Game> {
     let mut games = Vec::new();
+    let media = index_media(path);
     if let Ok(entries) = fs::read_dir(path) {
         for entry in entries.flatten() {
             let p = entry.path();
-            if p.is_file() && !p.file_name().unwrap().to_str().unwrap().starts_with('.') {
+            let file_name = p.file_name().unwrap().to_str().unwrap();
+            if p.is_file() && !file_name.starts_with('.') && file_name != GAMELIST_FILE {
                 let id = p.file_stem().unwrap().to_str().unwrap().to_string();
-                games.push(Game {
-                    id: id.clone(),
-                    name: id,
-                    path: p,
-                    year: "UNKNOWN".into(),
-                    manufacturer: "UNKNOWN".into(),
-                    players: "1".into(),
-                });
+                let mut game = Game::new(id.clone(), id.clone(), p);
+                (game.title, game.regions, game.revision) = parse_name_tags(&id);
+                game.media = media.get(&id).cloned().unwrap_or_default();
+                games.push(game);
             }
         }
     }
@@ -76,7 +90,19 @@ fn parse_mame_metadata(mame_path: &Path) -> Vec<Game> {
         }
     };
 
-    let mut reader = Reader::from_reader(xml_data.as_slice());
+    let games = parse_listxml(&xml_data, mame_path);
+    println!(
+        "✅ OSIRIS: MAME SCAN COMPLETE. {} MODULES VERIFIED.",
+        games.len()
+    );
+    games
+}
+
+/// The runnable machines of `mame -listxml` output whose ROM is in
+/// `mame_path`, sorted by description.
+fn parse_listxml(xml_data: &[u8], mame_path: &Path) -> Vec<Game> {
+    let media = index_media(mame_path);
+    let mut reader = Reader::from_reader(xml_data);
     reader.config_mut().trim_text(true);
 
     let mut games = Vec::new();
@@ -87,45 +113,91 @@ fn parse_mame_metadata(mame_path: &Path) -> Vec<Game> {
 
     loop {
         match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(e)) => {
+            // No </machine> follows, so it would take the next machine's
+            // children
+            Ok(Event::Empty(e)) if e.name().as_ref() == b"machine" => {
+                current_game = None;
+            }
+            // <input> and <driver> are self-closing unless they have children
+            Ok(Event::Start(e) | Event::Empty(e)) => {
                 let tag = String::from_utf8_lossy(e.name().as_ref()).to_string();
 
                 if tag == "machine" {
+                    current_game = None;
                     let mut id = String::new();
                     let mut runnable = true;
+                    let mut clone_of = None;
 
                     for attr in e.attributes().flatten() {
                         match attr.key.as_ref() {
                             b"name" => {
                                 id = String::from_utf8_lossy(attr.value.as_ref()).to_string();
                             }
-                            b"runnable" => {
-                                if attr.value.as_ref() == b"no" {
-                                    runnable = false;
-                                }
+                            b"runnable" if attr.value.as_ref() == b"no" => {
+                                runnable = false;
+                            }
+                            b"cloneof" => {
+                                clone_of =
+                                    Some(String::from_utf8_lossy(attr.value.as_ref()).to_string());
                             }
                             _ => {}
                         }
                     }
 
                     if runnable && !id.is_empty() {
-                        current_game = Some(Game {
-                            id: id.clone(),
-                            name: id.clone(),
-                            // We assume ROMs are in the mame dir with .zip extension
-                            path: mame_path.join(format!("{}.zip", id)),
-                            year: "".into(),
-                            manufacturer: "".into(),
-                            players: "1".into(),
-                        });
+                        // We assume ROMs are in the mame dir with .zip extension
+                        let mut game = Game::new(
+                            id.clone(),
+                            id.clone(),
+                            mame_path.join(format!("{}.zip", id)),
+                        );
+                        game.title = id.clone();
+                        game.clone_of = clone_of;
+                        game.media = media.get(&id).cloned().unwrap_or_default();
+                        current_game = Some(game);
                     }
-                } else if tag == "input" {
-                    if let Some(g) = &mut current_game {
-                        for attr in e.attributes().flatten() {
-                            if attr.key.as_ref() == b"players" {
-                                g.players =
-                                    String::from_utf8_lossy(attr.value.as_ref()).to_string();
-                            }
+                } else if tag == "input"
+                    && let Some(g) = &mut current_game
+                {
+                    for attr in e.attributes().flatten() {
+                        if attr.key.as_ref() == b"players"
+                            && let Ok(players) = std::str::from_utf8(attr.value.as_ref())
+                                .unwrap_or("")
+                                .parse::<u8>()
+                        {
+                            g.max_players = players.max(1);
+                        }
+                    }
+                } else if tag == "control"
+                    && let Some(g) = &mut current_game
+                {
+                    let mut control_type = String::new();
+                    let mut control = Control::default();
+                    for attr in e.attributes().flatten() {
+                        let value = String::from_utf8_lossy(attr.value.as_ref()).to_string();
+                        match attr.key.as_ref() {
+                            b"type" => control_type = value,
+                            b"player" => control.player = value.parse().unwrap_or(1),
+                            b"buttons" => control.buttons = value.parse().unwrap_or(0),
+                            b"ways" => control.ways = parse_ways(&value),
+                            _ => {}
+                        }
+                    }
+                    if let Some(kind) = ControlKind::from_mame(&control_type) {
+                        control.kind = kind;
+                        g.controls.push(control);
+                    }
+                } else if tag == "driver"
+                    && let Some(g) = &mut current_game
+                {
+                    for attr in e.attributes().flatten() {
+                        if attr.key.as_ref() == b"status" {
+                            g.status = match attr.value.as_ref() {
+                                b"good" => Some(EmulationStatus::Good),
+                                b"imperfect" => Some(EmulationStatus::Imperfect),
+                                b"preliminary" => Some(EmulationStatus::Preliminary),
+                                _ => None,
+                            };
                         }
                     }
                 }
@@ -135,20 +207,23 @@ fn parse_mame_metadata(mame_path: &Path) -> Vec<Game> {
                 if let Some(g) = &mut current_game {
                     let val = e.escape_ascii().to_string();
                     match current_tag.as_str() {
-                        "description" => g.name = val,
-                        "year" => g.year = val,
-                        "manufacturer" => g.manufacturer = val,
+                        "description" => {
+                            (g.title, g.regions, g.revision) = parse_name_tags(&val);
+                            g.name = val;
+                        }
+                        "year" => g.release_date = val.parse().ok(),
+                        "manufacturer" => g.developer = Some(val),
                         _ => {}
                     }
                 }
             }
             Ok(Event::End(e)) => {
-                if e.name().as_ref() == b"machine" {
-                    if let Some(g) = current_game.take() {
-                        // CRITICAL: Only add the game if the actual ROM file exists on disk
-                        if g.path.exists() {
-                            games.push(g);
-                        }
+                if e.name().as_ref() == b"machine"
+                    && let Some(g) = current_game.take()
+                {
+                    // CRITICAL: Only add the game if the actual ROM file exists on disk
+                    if g.path.exists() {
+                        games.push(g);
                     }
                 }
                 current_tag.clear();
@@ -163,12 +238,338 @@ fn parse_mame_metadata(mame_path: &Path) -> Vec<Game> {
         buf.clear();
     }
 
-    println!(
-        "✅ OSIRIS: MAME SCAN COMPLETE. {} MODULES VERIFIED.",
-        games.len()
-    );
-
     // Sort MAME games by their clean description/name
     games.sort_by(|a, b| a.name.cmp(&b.name));
     games
 }
+
+/// The directions of a MAME joystick `ways` attribute ("8", "strict8",
+/// "vertical2", "5 (half8)"...).
+pub fn parse_ways(ways: &str) -> Option<u8> {
+    let digits: String = ways
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+const GAMELIST_FILE: &str = "gamelist.xml";
+
+/// `<system dir>/images/<id>-<suffix>.<ext>`; "-image" is the original
+/// OSIRIS convention.
+const MEDIA_SUFFIXES: [(&str, MediaKind); 15] = [
+    ("boxfront", MediaKind::BoxFront),
+    ("box", MediaKind::BoxFront),
+    ("cover", MediaKind::BoxFront),
+    ("boxback", MediaKind::BoxBack),
+    ("marquee", MediaKind::Marquee),
+    ("wheel", MediaKind::Marquee),
+    ("title", MediaKind::TitleScreen),
+    ("titlescreen", MediaKind::TitleScreen),
+    ("snap", MediaKind::Snap),
+    ("screenshot", MediaKind::Snap),
+    ("image", MediaKind::Snap),
+    ("cart", MediaKind::Cartridge),
+    ("cartridge", MediaKind::Cartridge),
+    ("manual", MediaKind::Manual),
+    ("video", MediaKind::Video),
+];
+
+/// Stores an uploaded image as `<system dir>/images/<id>-<suffix>.<ext>`,
+/// with the extension of the format it is in. Returns where it went.
+pub fn save_media(
+    system_dir: &Path,
+    id: &str,
+    kind: MediaKind,
+    data: &[u8],
+) -> Result<PathBuf, String> {
+    let format = image::guess_format(data).map_err(|e| e.to_string())?;
+    let (suffix, _) = MEDIA_SUFFIXES
+        .iter()
+        .find(|&&(_, k)| k == kind)
+        .ok_or_else(|| format!("no file name for {:?} media", kind))?;
+    let ext = format.extensions_str().first().unwrap_or(&"img");
+
+    let dir = system_dir.join("images");
+    let path = dir.join(format!("{}-{}.{}", id, suffix, ext));
+    fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&path, data))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// ES-DE style `<system dir>/media/<folder>/<id>.<ext>`.
+const MEDIA_FOLDERS: [(&str, MediaKind); 8] = [
+    ("covers", MediaKind::BoxFront),
+    ("backcovers", MediaKind::BoxBack),
+    ("marquees", MediaKind::Marquee),
+    ("titlescreens", MediaKind::TitleScreen),
+    ("screenshots", MediaKind::Snap),
+    ("physicalmedia", MediaKind::Cartridge),
+    ("manuals", MediaKind::Manual),
+    ("videos", MediaKind::Video),
+];
+
+/// Media of every game in a system directory, keyed by game id. Paths listed
+/// in `gamelist.xml` win over the ones found by naming convention.
+fn index_media(system_dir: &Path) -> HashMap<String, MediaPaths> {
+    let mut index: HashMap<String, MediaPaths> = HashMap::new();
+    let mut add = |id: &str, kind: MediaKind, path: PathBuf| {
+        let media = index.entry(id.to_string()).or_default();
+        if media.get(kind).is_none() {
+            media.set(kind, path);
+        }
+    };
+
+    for file in list_files(&system_dir.join("images")) {
+        let stem = file.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        if let Some((id, suffix)) = stem.rsplit_once('-')
+            && let Some(&(_, kind)) = MEDIA_SUFFIXES
+                .iter()
+                .find(|(s, _)| s.eq_ignore_ascii_case(suffix))
+        {
+            add(id, kind, file.clone());
+        }
+    }
+
+    for (folder, kind) in MEDIA_FOLDERS {
+        for file in list_files(&system_dir.join("media").join(folder)) {
+            if let Some(id) = file.file_stem().and_then(|s| s.to_str()) {
+                add(id, kind, file.clone());
+            }
+        }
+    }
+
+    for (id, kind, path) in read_gamelist_media(system_dir) {
+        index.entry(id).or_default().set(kind, path);
+    }
+    index
+}
+
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.is_file())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// An EmulationStation `gamelist.xml` entry; only the media paths are used.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct GameListEntry {
+    path: String,
+    image: Option<String>,
+    thumbnail: Option<String>,
+    marquee: Option<String>,
+    wheel: Option<String>,
+    titleshot: Option<String>,
+    boxback: Option<String>,
+    cartridge: Option<String>,
+    manual: Option<String>,
+    video: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct GameList {
+    game: Vec<GameListEntry>,
+}
+
+fn read_gamelist_media(system_dir: &Path) -> Vec<(String, MediaKind, PathBuf)> {
+    let Ok(xml) = fs::read_to_string(system_dir.join(GAMELIST_FILE)) else {
+        return Vec::new();
+    };
+    let list: GameList = match quick_xml::de::from_str(&xml) {
+        Ok(list) => list,
+        Err(e) => {
+            println!(
+                "⚠️ OSIRIS: {} PARSE ERROR IN {:?}: {}",
+                GAMELIST_FILE, system_dir, e
+            );
+            return Vec::new();
+        }
+    };
+
+    // Paths are relative to the system directory ("./images/sonic.png")
+    let resolve = |p: &str| system_dir.join(p.trim_start_matches("./"));
+    let mut media = Vec::new();
+    for entry in list.game {
+        let Some(id) = Path::new(&entry.path).file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let paths = [
+            (MediaKind::Snap, &entry.image),
+            (MediaKind::BoxFront, &entry.thumbnail),
+            (MediaKind::Marquee, &entry.marquee),
+            (MediaKind::Marquee, &entry.wheel),
+            (MediaKind::TitleScreen, &entry.titleshot),
+            (MediaKind::BoxBack, &entry.boxback),
+            (MediaKind::Cartridge, &entry.cartridge),
+            (MediaKind::Manual, &entry.manual),
+            (MediaKind::Video, &entry.video),
+        ];
+        for (kind, path) in paths {
+            if let Some(path) = path.as_deref().filter(|p| !p.is_empty()) {
+                media.push((id.to_string(), kind, resolve(path)));
+            }
+        }
+    }
+    media
+}
+
+/// Splits a No-Intro/GoodTools style name such as "Sonic (USA, Europe) (Rev 1)"
+/// or a MAME description such as "Street Fighter II (World 910522)" into its
+/// bare title, region tags and revision number.
+pub fn parse_name_tags(name: &str) -> (String, Vec<String>, u32) {
+    let mut regions = Vec::new();
+    let mut revision = 0;
+
+    let title_end = name.find(['(', '[']).unwrap_or(name.len());
+    let title = name[..title_end].trim().to_string();
+
+    for group in name[title_end..].split(['(', ')', '[', ']']) {
+        let group = group.trim();
+        if group.is_empty() {
+            continue;
+        }
+
+        if let Some(rev) = group.strip_prefix("Rev ") {
+            revision = parse_revision(rev);
+            continue;
+        }
+
+        for part in group.split(',') {
+            // MAME appends dates/versions after the region ("World 910522")
+            let word = part.split_whitespace().next().unwrap_or("");
+            let region = normalize_region(part.trim()).or_else(|| normalize_region(word));
+            if let Some(region) = region
+                && !regions.iter().any(|r| r == region)
+            {
+                regions.push(region.to_string());
+            }
+        }
+    }
+
+    (title, regions, revision)
+}
+
+fn parse_revision(rev: &str) -> u32 {
+    let rev = rev.trim();
+    if let Ok(n) = rev.parse::<u32>() {
+        return n;
+    }
+    match rev.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => c.to_ascii_uppercase() as u32 - 'A' as u32 + 1,
+        _ => 0,
+    }
+}
+
+fn normalize_region(tag: &str) -> Option<&'static str> {
+    let region = match tag {
+        "USA" | "US" | "U" => "USA",
+        "Europe" | "E" => "Europe",
+        "Japan" | "J" => "Japan",
+        "World" | "W" => "World",
+        "Asia" => "Asia",
+        "Australia" | "A" => "Australia",
+        "Brazil" | "B" => "Brazil",
+        "Canada" => "Canada",
+        "China" | "C" => "China",
+        "France" | "F" => "France",
+        "Germany" | "G" => "Germany",
+        "Hong Kong" => "Hong Kong",
+        "Italy" | "I" => "Italy",
+        "Korea" | "K" => "Korea",
+        "Netherlands" => "Netherlands",
+        "Spain" | "S" => "Spain",
+        "Sweden" => "Sweden",
+        "Taiwan" => "Taiwan",
+        "UK" => "UK",
+        _ => return None,
+    };
+    Some(region)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_tags_give_title_regions_and_revision() {
+        assert_eq!(
+            parse_name_tags("Super Mario Bros. (USA, Europe)"),
+            (
+                "Super Mario Bros.".to_string(),
+                vec!["USA".to_string(), "Europe".to_string()],
+                0
+            )
+        );
+        assert_eq!(
+            parse_name_tags("Zelda no Densetsu (Japan) (Rev 1)"),
+            (
+                "Zelda no Densetsu".to_string(),
+                vec!["Japan".to_string()],
+                1
+            )
+        );
+        assert_eq!(parse_name_tags("Contra (U) [!] (Rev A)").2, 1);
+        // MAME appends versions to the region
+        assert_eq!(
+            parse_name_tags("Street Fighter II (World 910522)").1,
+            ["World"]
+        );
+        assert_eq!(
+            parse_name_tags("Action 52 (Unl) [b1]"),
+            ("Action 52".to_string(), Vec::new(), 0)
+        );
+    }
+
+    #[test]
+    fn regions_are_normalized() {
+        assert_eq!(normalize_region("U"), Some("USA"));
+        assert_eq!(normalize_region("Europe"), Some("Europe"));
+        assert_eq!(normalize_region("Hong Kong"), Some("Hong Kong"));
+        assert_eq!(normalize_region("Atlantis"), None);
+        assert_eq!(normalize_region("usa"), None);
+    }
+
+    #[test]
+    fn self_closing_machine_keeps_no_children() {
+        let dir = std::env::temp_dir().join(format!("osiris-listxml-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for id in ["empty", "pacman"] {
+            fs::write(dir.join(format!("{}.zip", id)), b"").unwrap();
+        }
+        let xml = br#"<mame>
+            <machine name="empty"/>
+            <machine name="bios" runnable="no">
+                <description>Some BIOS</description>
+                <input players="4"/>
+            </machine>
+            <machine name="pacman">
+                <description>Pac-Man (USA)</description>
+                <year>1980</year>
+                <input players="2"><control type="joy" ways="4"/></input>
+                <driver status="good"/>
+            </machine>
+        </mame>"#;
+
+        let games = parse_listxml(xml, &dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(games.len(), 1);
+        let pacman = &games[0];
+        assert_eq!(
+            (pacman.id.as_str(), pacman.title.as_str()),
+            ("pacman", "Pac-Man")
+        );
+        assert_eq!(pacman.max_players, 2);
+        assert_eq!(pacman.controls.len(), 1);
+        assert_eq!(pacman.status, Some(EmulationStatus::Good));
+    }
+}
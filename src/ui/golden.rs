@@ -0,0 +1,359 @@
+//! Golden-image tests: widgets are driven with scripted commands, rendered
+//! offscreen and compared to the reference PNGs in `tests/golden`.
+//!
+//! Run with `OSIRIS_UPDATE_GOLDEN=1 cargo test` to (re)write the references
+//! after an intended change. A failing comparison leaves the rendered frame
+//! and a diff image (differing pixels in red) in `target/golden`.
+
+use crate::commands::{ControlCommand, UiEvent};
+use crate::models::{Game, ReleaseDate, RomLibrary, SharedLibrary, System};
+use crate::ui::images::{ImageCache, SharedImages};
+use crate::ui::renderer;
+use crate::ui::theme::Theme;
+use crate::ui::tui::{GridSize, MIN_GRID_ROWS, TuiEngine};
+use crate::ui::widgets::common::Widget;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use tiny_skia::{Pixmap, PremultipliedColorU8};
+
+/// Small enough to keep the references light, large enough to read.
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 360;
+/// Per channel difference still counted as the same pixel (antialiasing).
+const CHANNEL_TOLERANCE: u8 = 16;
+/// Share of pixels allowed to differ beyond `CHANNEL_TOLERANCE`.
+const MAX_DIFF_RATIO: f64 = 0.001;
+
+fn game(name: &str, year: u16, developer: &str, players: u8) -> Game {
+    let mut game = Game::new(
+        name.to_lowercase().replace(' ', ""),
+        name.to_string(),
+        PathBuf::from(format!("{}.zip", name)),
+    );
+    game.title = name.to_string();
+    game.release_date = Some(ReleaseDate {
+        year,
+        month: None,
+        day: None,
+    });
+    game.developer = Some(developer.to_string());
+    game.max_players = players;
+    game
+}
+
+fn system(name: &str, games: Vec<Game>) -> System {
+    System {
+        name: name.to_string(),
+        games,
+        sort_mode: Default::default(),
+        filter: Default::default(),
+    }
+}
+
+/// Three systems with a handful of games each; no media, so nothing depends
+/// on image decoding.
+pub fn fixture_roms() -> RomLibrary {
+    let mut arcade = vec![
+        game("Galaxian", 1979, "Namco", 2),
+        game("Tetris", 1988, "Atari Games", 2),
+        game("Pac-Man", 1980, "Namco", 2),
+        game("Street Fighter II", 1991, "Capcom", 2),
+        game("Bubble Bobble", 1986, "Taito", 2),
+    ];
+    arcade[1].favorite = true;
+    arcade[3].rating = Some(0.9);
+    arcade[3].play_count = 12;
+
+    let mut library = RomLibrary::new();
+    library.systems = vec![
+        system("MAME", arcade),
+        system(
+            "NES",
+            vec![
+                game("Super Mario Bros", 1985, "Nintendo", 2),
+                game("Mega Man 2", 1988, "Capcom", 1),
+                game("Contra", 1988, "Konami", 2),
+            ],
+        ),
+        system(
+            "SNES",
+            vec![
+                game("Super Metroid", 1994, "Nintendo", 1),
+                game("Chrono Trigger", 1995, "Square", 1),
+            ],
+        ),
+    ];
+    library
+}
+
+pub fn fixture_library() -> SharedLibrary {
+    Rc::new(RefCell::new(fixture_roms()))
+}
+
+pub fn fixture_images() -> SharedImages {
+    Rc::new(RefCell::new(ImageCache::new(8)))
+}
+
+pub fn engine() -> TuiEngine {
+    TuiEngine::new(
+        include_bytes!("../../fonts/JetBrainsMono-Regular.ttf"),
+        Theme::default(),
+        GridSize::Rows(MIN_GRID_ROWS),
+    )
+}
+
+/// Sends an event through the tree the way the app does, following up on
+/// the events it raises in turn.
+pub fn dispatch(widget: &mut dyn Widget, event: UiEvent) {
+    // In the app the first frame is painted before any input arrives
+    renderer::lay_out(&engine(), widget, WIDTH, HEIGHT);
+    let mut queue = VecDeque::from([event]);
+    while let Some(event) = queue.pop_front() {
+        match event {
+            UiEvent::None => {}
+            UiEvent::Batch(events) => queue.extend(events),
+            event => queue.push_back(widget.handle_ui_event(event)),
+        }
+    }
+}
+
+/// Feeds `commands` to the tree, dispatching what each one raises.
+pub fn drive(widget: &mut dyn Widget, commands: &[ControlCommand]) {
+    renderer::lay_out(&engine(), widget, WIDTH, HEIGHT);
+    for cmd in commands {
+        let event = widget.handle_command(cmd.clone());
+        dispatch(widget, event);
+    }
+}
+
+pub fn render(widget: &mut dyn Widget) -> Pixmap {
+    renderer::render_offscreen(&engine(), widget, WIDTH, HEIGHT).expect("offscreen pixmap")
+}
+
+/// Compares `frame` to the reference `tests/golden/<name>.png`.
+pub fn assert_golden(name: &str, frame: &Pixmap) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let reference = root.join("tests/golden").join(format!("{}.png", name));
+
+    if std::env::var_os("OSIRIS_UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(reference.parent().unwrap()).unwrap();
+        frame.save_png(&reference).unwrap();
+        return;
+    }
+
+    let Ok(expected) = Pixmap::load_png(&reference) else {
+        panic!(
+            "missing reference {}; run with OSIRIS_UPDATE_GOLDEN=1 to create it",
+            reference.display()
+        );
+    };
+
+    let out_dir = root.join("target/golden");
+    let failure = |reason: String| -> ! {
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let actual = out_dir.join(format!("{}.actual.png", name));
+        frame.save_png(&actual).unwrap();
+        if let Some(diff) = diff_image(&expected, frame) {
+            diff.save_png(out_dir.join(format!("{}.diff.png", name)))
+                .unwrap();
+        }
+        panic!(
+            "{}: {} (rendered frame and diff in {})",
+            name,
+            reason,
+            out_dir.display()
+        );
+    };
+
+    if (expected.width(), expected.height()) != (frame.width(), frame.height()) {
+        failure(format!(
+            "size {}x{} differs from the reference {}x{}",
+            frame.width(),
+            frame.height(),
+            expected.width(),
+            expected.height()
+        ));
+    }
+
+    let differing = expected
+        .pixels()
+        .iter()
+        .zip(frame.pixels())
+        .filter(|(a, b)| !same_pixel(a, b))
+        .count();
+    let allowed = (expected.pixels().len() as f64 * MAX_DIFF_RATIO) as usize;
+    if differing > allowed {
+        failure(format!(
+            "{} pixels differ from the reference, at most {} allowed",
+            differing, allowed
+        ));
+    }
+}
+
+fn same_pixel(a: &PremultipliedColorU8, b: &PremultipliedColorU8) -> bool {
+    let close = |x: u8, y: u8| x.abs_diff(y) <= CHANNEL_TOLERANCE;
+    close(a.red(), b.red()) && close(a.green(), b.green()) && close(a.blue(), b.blue())
+}
+
+/// The reference faded to grey, with the differing pixels in red.
+fn diff_image(expected: &Pixmap, actual: &Pixmap) -> Option<Pixmap> {
+    let mut diff = Pixmap::new(expected.width(), expected.height())?;
+    let red = PremultipliedColorU8::from_rgba(255, 0, 0, 255)?;
+    for ((dst, a), b) in diff
+        .pixels_mut()
+        .iter_mut()
+        .zip(expected.pixels())
+        .zip(actual.pixels())
+    {
+        *dst = if same_pixel(a, b) {
+            let grey = ((a.red() as u16 + a.green() as u16 + a.blue() as u16) / 6) as u8;
+            PremultipliedColorU8::from_rgba(grey, grey, grey, 255)?
+        } else {
+            red
+        };
+    }
+    Some(diff)
+}
+
+mod tests {
+    use super::*;
+    use crate::commands::{ActionCommand, NavigationCommand};
+    use crate::config::Config;
+    use crate::models::{Cabinet, Control, ControlKind, Incompatible};
+    use crate::ui::layout::Layout;
+    use crate::ui::widgets::panel::SplitPanelWidget;
+    use crate::ui::widgets::{CarouselWidget, GameWidget, ListWidget};
+
+    const DOWN: ControlCommand = ControlCommand::Navigation(NavigationCommand::Down);
+    const RIGHT: ControlCommand = ControlCommand::Navigation(NavigationCommand::Right);
+
+    #[test]
+    fn list_selection_moves_down() {
+        let mut list = ListWidget::new(fixture_library(), fixture_images(), &Config::default());
+        drive(&mut list, &[DOWN, DOWN]);
+        assert_golden("list_selection", &render(&mut list));
+    }
+
+    #[test]
+    fn list_sorted_by_year() {
+        let mut list = ListWidget::new(fixture_library(), fixture_images(), &Config::default());
+        drive(
+            &mut list,
+            &[ControlCommand::Action(ActionCommand::CycleSort)],
+        );
+        assert_golden("list_sorted_by_year", &render(&mut list));
+    }
+
+    #[test]
+    fn list_dims_what_the_cabinet_cant_play() {
+        let library = fixture_library();
+        {
+            let mut library = library.borrow_mut();
+            let arcade = &mut library.systems[0].games;
+            // Needs a trackball, then more buttons than the panel has
+            arcade[1].controls = vec![Control {
+                kind: ControlKind::Trackball,
+                buttons: 1,
+                ..Control::default()
+            }];
+            arcade[4].controls = vec![Control {
+                kind: ControlKind::Joystick,
+                buttons: 8,
+                ways: Some(8),
+                ..Control::default()
+            }];
+        }
+        let mut config = Config {
+            cabinet: Some(Cabinet::default()),
+            ..Config::default()
+        };
+        let mut list = ListWidget::new(library.clone(), fixture_images(), &config);
+        assert_golden("list_cabinet_dim", &render(&mut list));
+
+        config.cabinet.as_mut().unwrap().incompatible = Incompatible::Hide;
+        let list = ListWidget::new(library, fixture_images(), &config);
+        assert_eq!(list.entries.len(), 3);
+    }
+
+    #[test]
+    fn carousel_wraps_around() {
+        let mut carousel = CarouselWidget::new(fixture_library());
+        drive(&mut carousel, &[RIGHT, RIGHT]);
+        assert_golden("carousel_wraps", &render(&mut carousel));
+    }
+
+    #[test]
+    fn game_details() {
+        let mut game = GameWidget::new(fixture_library(), fixture_images());
+        dispatch(&mut game, UiEvent::GameChanged(Some(3)));
+        assert_golden("game_details", &render(&mut game));
+    }
+
+    #[test]
+    fn game_history_scrolls() {
+        let library = fixture_library();
+        library.borrow_mut().systems[0].games[3].history = Some(
+            "Street Fighter II: The World Warrior (c) 1991 Capcom.\n\n\
+             Eight world warriors fight for the title, each with special moves \
+             performed by joystick motions and button presses. "
+                .repeat(4),
+        );
+        let mut game = GameWidget::new(library, fixture_images());
+        dispatch(&mut game, UiEvent::GameChanged(Some(3)));
+        drive(
+            &mut game,
+            &[ControlCommand::Action(ActionCommand::ScrollTextDown)],
+        );
+        assert_golden("game_history", &render(&mut game));
+    }
+
+    #[test]
+    fn game_control_panel() {
+        let library = fixture_library();
+        {
+            let mut library = library.borrow_mut();
+            let sf2 = &mut library.systems[0].games[3];
+            sf2.controls = (1..=2)
+                .map(|player| Control {
+                    kind: ControlKind::Joystick,
+                    player,
+                    buttons: 6,
+                    ways: Some(8),
+                })
+                .collect();
+            sf2.simultaneous_players = Some(2);
+            sf2.button_labels = vec!["Jab Punch".into(), "Strong Punch".into()];
+        }
+        let mut game = GameWidget::new(library, fixture_images());
+        dispatch(&mut game, UiEvent::GameChanged(Some(3)));
+        assert_golden("game_controls", &render(&mut game));
+    }
+
+    #[test]
+    fn split_list_follows_into_details() {
+        let library = fixture_library();
+        let images = fixture_images();
+        let mut split = SplitPanelWidget::new(
+            ListWidget::new(library.clone(), images.clone(), &Config::default()),
+            GameWidget::new(library, images),
+            35,
+            true,
+            false,
+        );
+        dispatch(&mut split, UiEvent::SystemChanged(1));
+        drive(&mut split, &[DOWN]);
+        assert_golden("split_list_details", &render(&mut split));
+    }
+
+    #[test]
+    fn default_layout_after_system_change() {
+        let library = fixture_library();
+        let images = fixture_images();
+        let mut root = Layout::default().build(&library, &images, &Config::default());
+        dispatch(&mut root, UiEvent::SystemChanged(0));
+        drive(&mut root, &[RIGHT, DOWN]);
+        assert_golden("default_layout", &render(&mut root));
+    }
+}
@@ -0,0 +1,332 @@
+use image::codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder};
+use image::{AnimationDecoder, Frames, ImageFormat, RgbaImage};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tiny_skia::{IntSize, Pixmap, PixmapMut, PixmapPaint, Transform};
+
+/// Longer animations are cut short so one preview can't eat the memory of
+/// the whole cache.
+const MAX_FRAMES: usize = 300;
+/// Browsers play frames with a (near) zero delay at 10 fps, and so do we.
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+/// The image cache as shared by the widgets of the UI thread.
+pub type SharedImages = Rc<RefCell<ImageCache>>;
+
+pub enum ImageState<'a> {
+    Ready(&'a Image),
+    Loading,
+    Missing,
+}
+
+/// A decoded still image, or every frame of an animated GIF/APNG/WebP.
+pub struct Image {
+    frames: Vec<(Pixmap, Duration)>,
+    loop_length: Duration,
+}
+
+impl Image {
+    fn still(pixmap: Pixmap) -> Self {
+        Self {
+            frames: vec![(pixmap, Duration::ZERO)],
+            loop_length: Duration::ZERO,
+        }
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+
+    /// The frame showing `elapsed` into the (looping) playback, and how long
+    /// it stays up. Stills stay up forever.
+    pub fn frame_at(&self, elapsed: Duration) -> (&Pixmap, Option<Duration>) {
+        if !self.is_animated() {
+            return (&self.frames[0].0, None);
+        }
+
+        let loop_length = self.loop_length.as_nanos();
+        let mut pos = Duration::from_nanos((elapsed.as_nanos() % loop_length) as u64);
+        for (pixmap, delay) in &self.frames {
+            if pos < *delay {
+                return (pixmap, Some(*delay - pos));
+            }
+            pos -= *delay;
+        }
+        (&self.frames[0].0, Some(self.frames[0].1))
+    }
+}
+
+enum Decoded {
+    Done(Option<Image>),
+    Skipped, // no longer wanted by the time a worker picked it up
+}
+
+/// Decodes images on the rayon pool and keeps the most recently used ones.
+/// Only the paths of the latest `focus` call are decoded, so scrolling
+/// quickly through the list doesn't queue up work for games already passed.
+pub struct ImageCache {
+    capacity: usize,
+    /// The most important path of the latest `focus` call. It is never
+    /// evicted, so prefetching its neighbours into a small cache can't push
+    /// out the image on screen.
+    focused: Option<PathBuf>,
+    entries: HashMap<PathBuf, Option<Image>>, // None: could not be decoded
+    lru: VecDeque<PathBuf>,
+    pending: HashSet<PathBuf>,
+    wanted: Arc<Mutex<HashSet<PathBuf>>>,
+    tx: Sender<(PathBuf, Decoded)>,
+    rx: Receiver<(PathBuf, Decoded)>,
+}
+
+impl ImageCache {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, rx) = channel();
+        Self {
+            capacity: capacity.max(1),
+            focused: None,
+            entries: HashMap::new(),
+            lru: VecDeque::new(),
+            pending: HashSet::new(),
+            wanted: Arc::new(Mutex::new(HashSet::new())),
+            tx,
+            rx,
+        }
+    }
+
+    pub fn get(&self, path: &Path) -> ImageState<'_> {
+        match self.entries.get(path) {
+            Some(Some(pixmap)) => ImageState::Ready(pixmap),
+            Some(None) => ImageState::Missing,
+            None if self.pending.contains(path) => ImageState::Loading,
+            None => ImageState::Missing,
+        }
+    }
+
+    /// True while decodes are queued or running.
+    pub fn is_loading(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Replaces the set of wanted images (most important first) and queues
+    /// the ones not cached yet.
+    pub fn focus(&mut self, paths: &[PathBuf]) {
+        {
+            let mut wanted = self.wanted.lock().unwrap();
+            wanted.clear();
+            wanted.extend(paths.iter().cloned());
+        }
+        self.focused = paths.first().cloned();
+        for path in paths {
+            self.request(path);
+        }
+    }
+
+    pub fn request(&mut self, path: &Path) {
+        if self.entries.contains_key(path) {
+            self.touch(path);
+            return;
+        }
+        self.wanted.lock().unwrap().insert(path.to_path_buf());
+        if !self.pending.insert(path.to_path_buf()) {
+            return;
+        }
+
+        let path = path.to_path_buf();
+        let tx = self.tx.clone();
+        let wanted = self.wanted.clone();
+        rayon::spawn(move || {
+            let result = if wanted.lock().unwrap().contains(&path) {
+                Decoded::Done(decode(&path))
+            } else {
+                Decoded::Skipped
+            };
+            let _ = tx.send((path, result));
+        });
+    }
+
+    /// Drops the decoded copy of `path`, e.g. after the file was replaced.
+    pub fn forget(&mut self, path: &Path) {
+        if self.entries.remove(path).is_some() {
+            self.lru.retain(|p| p != path);
+        }
+    }
+
+    /// Moves finished decodes into the cache. Returns true if any arrived.
+    pub fn poll(&mut self) -> bool {
+        let mut arrived = false;
+        while let Ok((path, result)) = self.rx.try_recv() {
+            self.pending.remove(&path);
+            match result {
+                Decoded::Done(image) => {
+                    self.insert(path, image);
+                    arrived = true;
+                }
+                // Focused again after the worker skipped it, while the
+                // request still looked pending
+                Decoded::Skipped if self.wanted.lock().unwrap().contains(&path) => {
+                    self.request(&path);
+                }
+                Decoded::Skipped => {}
+            }
+        }
+        arrived
+    }
+
+    fn insert(&mut self, path: PathBuf, image: Option<Image>) {
+        while self.lru.len() >= self.capacity {
+            let Some(pos) = self
+                .lru
+                .iter()
+                .position(|p| Some(p) != self.focused.as_ref())
+            else {
+                break;
+            };
+            if let Some(oldest) = self.lru.remove(pos) {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.lru.push_back(path.clone());
+        self.entries.insert(path, image);
+    }
+
+    fn touch(&mut self, path: &Path) {
+        if let Some(pos) = self.lru.iter().position(|p| p == path) {
+            let path = self.lru.remove(pos).unwrap();
+            self.lru.push_back(path);
+        }
+    }
+}
+
+/// Draws `image` scaled to fit (keeping its aspect ratio) and centered in
+/// the given pixel rect.
+pub fn draw_fit(pixmap: &mut PixmapMut, image: &Pixmap, x: f32, y: f32, w: f32, h: f32) {
+    let scale = (w / image.width() as f32).min(h / image.height() as f32);
+    let x_offset = (w - image.width() as f32 * scale) / 2.0;
+    let y_offset = (h - image.height() as f32 * scale) / 2.0;
+
+    let transform = Transform::from_scale(scale, scale).post_translate(x + x_offset, y + y_offset);
+    pixmap.draw_pixmap(
+        0,
+        0,
+        image.as_ref(),
+        &PixmapPaint::default(),
+        transform,
+        None,
+    );
+}
+
+/// Any format the `image` crate can read. GIF, APNG and WebP keep all of
+/// their frames; if the animation can't be decoded the first frame is used.
+fn decode(path: &Path) -> Option<Image> {
+    if let Some(image) = decode_animation(path) {
+        return Some(image);
+    }
+    to_pixmap(image::open(path).ok()?.to_rgba8()).map(Image::still)
+}
+
+fn decode_animation(path: &Path) -> Option<Image> {
+    let reader = || File::open(path).ok().map(BufReader::new);
+    let frames: Frames = match ImageFormat::from_path(path).ok()? {
+        ImageFormat::Gif => GifDecoder::new(reader()?).ok()?.into_frames(),
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(reader()?).ok()?;
+            if !decoder.is_apng().ok()? {
+                return None;
+            }
+            decoder.apng().ok()?.into_frames()
+        }
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(reader()?).ok()?;
+            if !decoder.has_animation() {
+                return None;
+            }
+            decoder.into_frames()
+        }
+        _ => return None,
+    };
+
+    let mut decoded = Vec::new();
+    for frame in frames.take(MAX_FRAMES) {
+        let frame = frame.ok()?;
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        let delay = Duration::from_micros(numer as u64 * 1000 / denom.max(1) as u64);
+        let delay = if delay < MIN_FRAME_DELAY {
+            DEFAULT_FRAME_DELAY
+        } else {
+            delay
+        };
+        decoded.push((to_pixmap(frame.into_buffer())?, delay));
+    }
+    if decoded.len() < 2 {
+        return None;
+    }
+
+    let loop_length = decoded.iter().map(|(_, delay)| *delay).sum();
+    Some(Image {
+        frames: decoded,
+        loop_length,
+    })
+}
+
+/// Premultiplies the pixels into a tiny-skia pixmap.
+fn to_pixmap(img: RgbaImage) -> Option<Pixmap> {
+    let size = IntSize::from_wh(img.width(), img.height())?;
+    let mut data = img.into_raw();
+
+    for px in data.chunks_exact_mut(4) {
+        let a = px[3] as u16;
+        if a < 255 {
+            px[0] = (px[0] as u16 * a / 255) as u8;
+            px[1] = (px[1] as u16 * a / 255) as u8;
+            px[2] = (px[2] as u16 * a / 255) as u8;
+        }
+    }
+    Pixmap::from_vec(data, size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefetch_keeps_the_focused_image() {
+        let mut cache = ImageCache::new(2);
+        let paths: Vec<_> = ["sel.png", "next.png", "prev.png"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        cache.focus(&paths);
+        for path in &paths {
+            cache.insert(path.clone(), None);
+        }
+        assert!(cache.entries.contains_key(&paths[0]));
+        assert_eq!(cache.entries.len(), 2);
+    }
+
+    #[test]
+    fn refocused_image_is_decoded_after_a_skip() {
+        let (a, b) = (PathBuf::from("a.png"), PathBuf::from("b.png"));
+        let mut cache = ImageCache::new(4);
+        cache.focus(std::slice::from_ref(&a));
+        cache.focus(std::slice::from_ref(&b));
+        cache.focus(std::slice::from_ref(&a));
+
+        // As if both workers ran while `b` was focused
+        for _ in 0..2 {
+            cache.rx.recv().unwrap();
+        }
+        cache.tx.send((a.clone(), Decoded::Skipped)).unwrap();
+        cache.tx.send((b.clone(), Decoded::Skipped)).unwrap();
+        cache.poll();
+        assert!(matches!(cache.get(&a), ImageState::Loading));
+        assert!(matches!(cache.get(&b), ImageState::Missing));
+    }
+}
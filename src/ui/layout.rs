@@ -0,0 +1,193 @@
+use crate::config::Config;
+use crate::models::SharedLibrary;
+use crate::ui::images::SharedImages;
+use crate::ui::widgets::common::Widget;
+use crate::ui::widgets::panel::SplitPanelWidget;
+use crate::ui::widgets::{CarouselWidget, GameWidget, ListWidget};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// The widget tree under the modal panels, read from the layout file, e.g.
+///
+/// ```xml
+/// <layout>
+///   <split direction="vertical" ratio="20">
+///     <carousel/>
+///     <split direction="horizontal" ratio="35">
+///       <list/>
+///       <game/>
+///     </split>
+///   </split>
+/// </layout>
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct Layout {
+    #[serde(rename = "$value")]
+    pub root: LayoutNode,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutNode {
+    Split(SplitNode),
+    Carousel,
+    List,
+    Game,
+}
+
+/// Two widgets side by side (`horizontal`) or the first above the second
+/// (`vertical`). The first one gets `ratio` percent of the space, or `ratio`
+/// grid cells with `unit="cells"`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SplitNode {
+    #[serde(rename = "@direction")]
+    pub direction: SplitDirection,
+    #[serde(rename = "@ratio")]
+    pub ratio: u32,
+    #[serde(rename = "@unit", default)]
+    pub unit: SplitUnit,
+    #[serde(rename = "$value")]
+    pub children: Vec<LayoutNode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitDirection {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitUnit {
+    #[default]
+    Percent,
+    Cells,
+}
+
+impl Default for Layout {
+    /// Systems across the top, the game list and details below.
+    fn default() -> Self {
+        let details = SplitNode {
+            direction: SplitDirection::Horizontal,
+            ratio: 35,
+            unit: SplitUnit::Percent,
+            children: vec![LayoutNode::List, LayoutNode::Game],
+        };
+        let root = SplitNode {
+            direction: SplitDirection::Vertical,
+            ratio: 20,
+            unit: SplitUnit::Percent,
+            children: vec![LayoutNode::Carousel, LayoutNode::Split(details)],
+        };
+        Self {
+            root: LayoutNode::Split(root),
+        }
+    }
+}
+
+impl Layout {
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let Ok(xml) = fs::read_to_string(path.as_ref()) else {
+            return Self::default();
+        };
+
+        let layout = quick_xml::de::from_str::<Layout>(&xml)
+            .map_err(|e| e.to_string())
+            .and_then(|layout| layout.validate().map(|_| layout));
+        match layout {
+            Ok(layout) => layout,
+            Err(e) => {
+                println!(
+                    "⚠️ OSIRIS: LAYOUT PARSE ERROR: {}. USING DEFAULT LAYOUT.",
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Nothing but the game list announces the selected game, so a layout
+    /// without one would leave the details stuck on the first game.
+    fn validate(&self) -> Result<(), String> {
+        self.root.validate()?;
+        if !self.root.has_list() {
+            return Err("the layout has no <list/>".to_string());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn build(
+        &self,
+        library: &SharedLibrary,
+        images: &SharedImages,
+        config: &Config,
+    ) -> Box<dyn Widget> {
+        self.root.build(library, images, config)
+    }
+}
+
+impl LayoutNode {
+    fn validate(&self) -> Result<(), String> {
+        let LayoutNode::Split(split) = self else {
+            return Ok(());
+        };
+        if split.children.len() != 2 {
+            return Err(format!(
+                "a split needs exactly 2 children, found {}",
+                split.children.len()
+            ));
+        }
+        if split.unit == SplitUnit::Percent && split.ratio > 100 {
+            return Err(format!("split ratio {}% is over 100%", split.ratio));
+        }
+        split.children.iter().try_for_each(LayoutNode::validate)
+    }
+
+    fn has_list(&self) -> bool {
+        match self {
+            LayoutNode::Split(split) => split.children.iter().any(LayoutNode::has_list),
+            LayoutNode::List => true,
+            LayoutNode::Carousel | LayoutNode::Game => false,
+        }
+    }
+
+    fn build(
+        &self,
+        library: &SharedLibrary,
+        images: &SharedImages,
+        config: &Config,
+    ) -> Box<dyn Widget> {
+        match self {
+            LayoutNode::Split(split) => Box::new(SplitPanelWidget::new(
+                split.children[0].build(library, images, config),
+                split.children[1].build(library, images, config),
+                split.ratio,
+                split.unit == SplitUnit::Percent,
+                split.direction == SplitDirection::Vertical,
+            )),
+            LayoutNode::Carousel => Box::new(CarouselWidget::new(library.clone())),
+            LayoutNode::List => Box::new(ListWidget::new(library.clone(), images.clone(), config)),
+            LayoutNode::Game => Box::new(GameWidget::new(library.clone(), images.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(xml: &str) -> Layout {
+        quick_xml::de::from_str(xml).unwrap()
+    }
+
+    #[test]
+    fn rejects_a_layout_without_a_list() {
+        let layout = parse(
+            r#"<layout><split direction="horizontal" ratio="50"><carousel/><game/></split></layout>"#,
+        );
+        assert!(layout.validate().is_err());
+        assert!(Layout::default().validate().is_ok());
+    }
+}
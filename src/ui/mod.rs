@@ -1,3 +1,8 @@
+#[cfg(test)]
+pub(crate) mod golden;
+pub mod images;
+pub mod layout;
 pub mod renderer;
+pub mod theme;
 pub mod tui;
 pub mod widgets;
@@ -1,19 +1,22 @@
-use softbuffer::{Context, Surface};
+use softbuffer::{Context, Rect as SurfaceRect, Surface};
 use std::{
     num::NonZeroU32,
     rc::Rc,
     time::{Duration, Instant},
 };
-use tiny_skia::{Color, PixmapMut};
+use tiny_skia::{Color, Paint, Pixmap, PixmapMut, Rect, Transform};
 use winit::window::Window;
 
-use crate::tui::{GRID_ROWS, TuiEngine};
-use crate::ui::widgets::common::Widget;
+use crate::tui::{Rotation, TuiEngine, TuiMetrics};
+use crate::ui::widgets::common::{CellRect, Widget};
 
 pub struct Renderer {
     pub tui: TuiEngine,
     pub context: Option<Context<Rc<Window>>>,
     pub surface: Option<Surface<Rc<Window>, Rc<Window>>>,
+    /// Widgets draw in RGBA here; copied to the surface's native format.
+    frame: Option<Pixmap>,
+    rotation: Rotation,
     pub last_frame_time: Duration,
     pub current_fps: u32,
     pub fps_counter: u32,
@@ -21,11 +24,13 @@ pub struct Renderer {
 }
 
 impl Renderer {
-    pub fn new(tui: TuiEngine) -> Self {
+    pub fn new(tui: TuiEngine, rotation: Rotation) -> Self {
         Self {
             tui,
             context: None,
             surface: None,
+            frame: None,
+            rotation,
             last_frame_time: Duration::ZERO,
             fps_timer: Instant::now(),
             current_fps: 0,
@@ -59,80 +64,70 @@ impl Renderer {
             )
             .unwrap();
 
+        let (frame_width, frame_height) = self.rotation.apply_size(buf_width, buf_height);
+        let mut full = false;
+        if self
+            .frame
+            .as_ref()
+            .is_none_or(|f| f.width() != frame_width || f.height() != frame_height)
+        {
+            self.frame = Pixmap::new(frame_width, frame_height);
+            full = true;
+        }
+
+        let metrics = self
+            .tui
+            .calculate_metrics(buf_width, buf_height, self.rotation);
+        place(root_widget, &metrics);
+        let mut damage = Vec::new();
+        root_widget.damage(&mut damage);
+        // Nothing changed, so the system asked for it (exposed, restored)
+        full |= damage.is_empty();
+
+        let Some(frame) = self.frame.as_mut() else {
+            return;
+        };
+        let stats_msg = format!(
+            " [ RENDER: {:>5.2?} | FPS: {:>3} ] ",
+            self.last_frame_time, self.current_fps
+        );
+        draw_screen(
+            &self.tui,
+            &mut frame.as_mut(),
+            &metrics,
+            root_widget,
+            (!full).then_some(damage.as_slice()),
+            Some(&stats_msg),
+        );
+
+        // The status bar changes every frame
+        damage.push(CellRect::new(0, metrics.rows - 1, metrics.cols, 1));
+
         let mut buffer = surface.buffer_mut().unwrap();
-        let raw_bytes: &mut [u8] = unsafe {
-            std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, buffer.len() * 4)
+        let whole = SurfaceRect {
+            x: 0,
+            y: 0,
+            width: NonZeroU32::new(buf_width).unwrap(),
+            height: NonZeroU32::new(buf_height).unwrap(),
         };
 
-        if let Some(mut pixmap) = PixmapMut::from_bytes(raw_bytes, buf_width, buf_height) {
-            let bg_color = Color::from_rgba8(5, 10, 0, 255);
-            let cyan = Color::from_rgba8(255, 255, 0, 255);
-            let green = Color::from_rgba8(0, 255, 0, 255);
-            let status_bg = Color::from_rgba8(20, 20, 20, 255);
-
-            pixmap.fill(bg_color);
-            let metrics = self.tui.calculate_metrics(buf_width, buf_height);
-
-            self.tui.draw_box(
-                &mut pixmap,
-                &metrics,
-                1,
-                1,
-                metrics.cols - 2,
-                GRID_ROWS - 2,
-                cyan,
-            );
-
-            let title = " OSIRIS MISSION TERMINAL ";
-            self.tui.draw_string(
-                &mut pixmap,
-                &metrics,
-                title,
-                (metrics.cols / 2).saturating_sub(title.len() / 2),
-                0,
-                cyan,
-            );
-
-            root_widget.set_rect(2, 2, metrics.cols - 4, GRID_ROWS - 4);
-            root_widget.draw(&mut pixmap, &self.tui, &metrics);
-
-            let bar_y = GRID_ROWS - 1;
-            let stats_msg = format!(
-                " [ RENDER: {:>5.2?} | FPS: {:>3} ] ",
-                self.last_frame_time, self.current_fps
-            );
-
-            self.tui.draw_string_ex(
-                &mut pixmap,
-                &metrics,
-                &" ".repeat(metrics.cols),
-                0,
-                bar_y,
-                Color::TRANSPARENT,
-                Some(status_bg),
-                1,
-            );
-            self.tui.draw_string(
-                &mut pixmap,
-                &metrics,
-                &stats_msg,
-                metrics.cols.saturating_sub(stats_msg.len()),
-                bar_y,
-                cyan,
-            );
-            self.tui.draw_string(
-                &mut pixmap,
-                &metrics,
-                " OSIRIS MISSION CONTROL",
-                0,
-                bar_y,
-                green,
-            );
+        // A buffer that is not the one presented last holds an older frame
+        if full || buffer.age() != 1 {
+            copy_to_surface(frame, &mut buffer, whole, self.rotation);
+            self.last_frame_time = start_time.elapsed();
+            buffer.present().unwrap();
+        } else {
+            let rects: Vec<SurfaceRect> = damage
+                .iter()
+                .filter_map(|cells| surface_rect(cells, &metrics, frame, self.rotation))
+                .collect();
+            for &rect in &rects {
+                copy_to_surface(frame, &mut buffer, rect, self.rotation);
+            }
+            self.last_frame_time = start_time.elapsed();
+            buffer.present_with_damage(&rects).unwrap();
         }
 
-        self.last_frame_time = start_time.elapsed();
-        buffer.present().unwrap();
-
         self.fps_counter += 1;
         if self.fps_timer.elapsed() >= Duration::from_secs(1) {
             self.current_fps = self.fps_counter;
@@ -141,3 +136,180 @@ impl Renderer {
         }
     }
 }
+
+/// Renders the widget tree into a new `width`x`height` pixmap without a
+/// window, e.g. for screenshots. The tree is drawn upright whatever the
+/// configured rotation.
+pub fn render_offscreen(
+    tui: &TuiEngine,
+    root_widget: &mut dyn Widget,
+    width: u32,
+    height: u32,
+) -> Option<Pixmap> {
+    let mut frame = Pixmap::new(width, height)?;
+    let metrics = lay_out(tui, root_widget, width, height);
+    root_widget.damage(&mut Vec::new());
+    draw_screen(tui, &mut frame.as_mut(), &metrics, root_widget, None, None);
+    Some(frame)
+}
+
+/// Gives the widget tree its place inside the screen border of an upright
+/// `width`x`height` screen.
+pub fn lay_out(
+    tui: &TuiEngine,
+    root_widget: &mut dyn Widget,
+    width: u32,
+    height: u32,
+) -> TuiMetrics {
+    let metrics = tui.calculate_metrics(width, height, Rotation::None);
+    place(root_widget, &metrics);
+    metrics
+}
+
+/// Fits the widget tree inside the screen border, however few cells the
+/// font leaves.
+fn place(root_widget: &mut dyn Widget, metrics: &TuiMetrics) {
+    root_widget.set_rect(
+        2,
+        2,
+        metrics.cols.saturating_sub(4),
+        metrics.rows.saturating_sub(4),
+    );
+}
+
+/// Draws the screen border, the widget tree and the status bar. Given
+/// `damage`, only the widgets overlapping it are cleared and painted again;
+/// `stats` goes at the right end of the status bar.
+fn draw_screen(
+    tui: &TuiEngine,
+    pixmap: &mut PixmapMut,
+    metrics: &TuiMetrics,
+    root_widget: &dyn Widget,
+    damage: Option<&[CellRect]>,
+    stats: Option<&str>,
+) {
+    let palette = &tui.theme.palette;
+
+    if let Some(damage) = damage {
+        let mut paint = Paint::default();
+        paint.set_color(palette.background);
+        for cells in damage {
+            if let Some(rect) = Rect::from_xywh(
+                cells.x as f32 * metrics.char_width,
+                cells.y as f32 * metrics.char_height,
+                cells.w as f32 * metrics.char_width,
+                cells.h as f32 * metrics.char_height,
+            ) {
+                pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+            }
+        }
+        root_widget.draw_damaged(pixmap, tui, metrics, damage);
+    } else {
+        pixmap.fill(palette.background);
+        tui.draw_box(
+            pixmap,
+            metrics,
+            1,
+            1,
+            metrics.cols.saturating_sub(2),
+            metrics.rows.saturating_sub(2),
+            palette.frame,
+        );
+
+        let title = &tui.theme.title;
+        tui.draw_string(
+            pixmap,
+            metrics,
+            title,
+            (metrics.cols / 2).saturating_sub(title.chars().count() / 2),
+            0,
+            palette.frame,
+        );
+
+        root_widget.draw(pixmap, tui, metrics);
+    }
+
+    let bar_y = metrics.rows - 1;
+    tui.draw_string_ex(
+        pixmap,
+        metrics,
+        &" ".repeat(metrics.cols),
+        0,
+        bar_y,
+        Color::TRANSPARENT,
+        Some(palette.status_bg),
+        1,
+    );
+    if let Some(stats) = stats {
+        tui.draw_string(
+            pixmap,
+            metrics,
+            stats,
+            metrics.cols.saturating_sub(stats.len()),
+            bar_y,
+            palette.frame,
+        );
+    }
+    tui.draw_string(
+        pixmap,
+        metrics,
+        &tui.theme.status_text,
+        0,
+        bar_y,
+        palette.accent,
+    );
+}
+
+/// The pixels of `cells` in surface buffer coordinates, rounded outwards.
+fn surface_rect(
+    cells: &CellRect,
+    metrics: &TuiMetrics,
+    frame: &Pixmap,
+    rotation: Rotation,
+) -> Option<SurfaceRect> {
+    let (fw, fh) = (frame.width(), frame.height());
+    let fx = ((cells.x as f32 * metrics.char_width) as u32).min(fw);
+    let fy = ((cells.y as f32 * metrics.char_height) as u32).min(fh);
+    let rw = (((cells.x + cells.w) as f32 * metrics.char_width).ceil() as u32).min(fw) - fx;
+    let rh = (((cells.y + cells.h) as f32 * metrics.char_height).ceil() as u32).min(fh) - fy;
+
+    let (x, y, w, h) = match rotation {
+        Rotation::None => (fx, fy, rw, rh),
+        Rotation::Cw90 => (fh - fy - rh, fx, rh, rw),
+        Rotation::Cw180 => (fw - fx - rw, fh - fy - rh, rw, rh),
+        Rotation::Cw270 => (fy, fw - fx - rw, rh, rw),
+    };
+    Some(SurfaceRect {
+        x,
+        y,
+        width: NonZeroU32::new(w)?,
+        height: NonZeroU32::new(h)?,
+    })
+}
+
+/// Writes the `rect` part of the surface buffer from `frame`, rotated.
+/// softbuffer wants 0RGB words; the frame is opaque, so premultiplied
+/// channels are the plain ones.
+fn copy_to_surface(frame: &Pixmap, buffer: &mut [u32], rect: SurfaceRect, rotation: Rotation) {
+    let pixels = frame.pixels();
+    let (fw, fh) = (frame.width() as usize, frame.height() as usize);
+    let width = match rotation {
+        Rotation::None | Rotation::Cw180 => fw,
+        Rotation::Cw90 | Rotation::Cw270 => fh,
+    };
+    let (x0, y0) = (rect.x as usize, rect.y as usize);
+    let (w, h) = (rect.width.get() as usize, rect.height.get() as usize);
+
+    for (y, row) in buffer.chunks_exact_mut(width).enumerate().skip(y0).take(h) {
+        for (x, dst) in row.iter_mut().enumerate().skip(x0).take(w) {
+            let (sx, sy) = match rotation {
+                Rotation::None => (x, y),
+                Rotation::Cw90 => (y, fh - 1 - x),
+                Rotation::Cw180 => (fw - 1 - x, fh - 1 - y),
+                Rotation::Cw270 => (fw - 1 - y, x),
+            };
+            let px = pixels[sy * fw + sx];
+            *dst = (px.red() as u32) << 16 | (px.green() as u32) << 8 | px.blue() as u32;
+        }
+    }
+}
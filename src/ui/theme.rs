@@ -0,0 +1,198 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use tiny_skia::Color;
+
+/// Colors, border glyphs and fixed texts of the UI, read from the theme file,
+/// e.g.
+///
+/// ```xml
+/// <theme>
+///   <title> ARCADE </title>
+///   <border>╔╗╚╝═║</border>
+///   <palette>
+///     <frame>#FFB000</frame>
+///     <highlight_bg>#402C00</highlight_bg>
+///   </palette>
+/// </theme>
+/// ```
+///
+/// Colors are plain RGB(A) hex; the renderer converts to the native pixel
+/// format.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub palette: Palette,
+    pub border: BorderGlyphs,
+    /// Centered in the top border of the screen.
+    pub title: String,
+    /// Left end of the status bar.
+    pub status_text: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Palette {
+    #[serde(with = "hex_color")]
+    pub background: Color,
+    /// Borders and panel titles.
+    #[serde(with = "hex_color")]
+    pub frame: Color,
+    /// Secondary information (platform, headers, captions).
+    #[serde(with = "hex_color")]
+    pub accent: Color,
+    #[serde(with = "hex_color")]
+    pub text: Color,
+    /// Unselected rows.
+    #[serde(with = "hex_color")]
+    pub text_dim: Color,
+    /// Placeholders ("NO VISUAL FEED") and hints.
+    #[serde(with = "hex_color")]
+    pub muted: Color,
+    /// Behind the selected row of lists, panels and the keyboard.
+    #[serde(with = "hex_color")]
+    pub highlight_bg: Color,
+    /// Behind the selected system of the carousel.
+    #[serde(with = "hex_color")]
+    pub system_highlight_bg: Color,
+    /// Backdrop of modal panels.
+    #[serde(with = "hex_color")]
+    pub panel_bg: Color,
+    /// Behind game media.
+    #[serde(with = "hex_color")]
+    pub image_bg: Color,
+    #[serde(with = "hex_color")]
+    pub status_bg: Color,
+    /// Laid over attract mode in dim mode; its alpha sets the dimming.
+    #[serde(with = "hex_color")]
+    pub dim: Color,
+}
+
+/// Box drawing characters, given in the theme file as one string in the
+/// order top-left, top-right, bottom-left, bottom-right, horizontal,
+/// vertical (`┌┐└┘─│`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct BorderGlyphs {
+    pub top_left: char,
+    pub top_right: char,
+    pub bottom_left: char,
+    pub bottom_right: char,
+    pub horizontal: char,
+    pub vertical: char,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            palette: Palette::default(),
+            border: BorderGlyphs::default(),
+            title: " OSIRIS MISSION TERMINAL ".into(),
+            status_text: " OSIRIS MISSION CONTROL".into(),
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            background: Color::from_rgba8(0, 10, 5, 255),
+            frame: Color::from_rgba8(0, 255, 255, 255),
+            accent: Color::from_rgba8(0, 255, 0, 255),
+            text: Color::from_rgba8(255, 255, 255, 255),
+            text_dim: Color::from_rgba8(180, 180, 180, 255),
+            muted: Color::from_rgba8(100, 100, 100, 255),
+            highlight_bg: Color::from_rgba8(0, 60, 60, 255),
+            system_highlight_bg: Color::from_rgba8(60, 60, 0, 255),
+            panel_bg: Color::from_rgba8(5, 20, 15, 255),
+            image_bg: Color::from_rgba8(5, 15, 5, 255),
+            status_bg: Color::from_rgba8(20, 20, 20, 255),
+            dim: Color::from_rgba8(0, 0, 0, 170),
+        }
+    }
+}
+
+impl Default for BorderGlyphs {
+    fn default() -> Self {
+        "┌┐└┘─│".parse().unwrap()
+    }
+}
+
+impl std::str::FromStr for BorderGlyphs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let glyphs: Vec<char> = s.trim().chars().collect();
+        let &[
+            top_left,
+            top_right,
+            bottom_left,
+            bottom_right,
+            horizontal,
+            vertical,
+        ] = glyphs.as_slice()
+        else {
+            return Err(format!("expected 6 border glyphs, got {:?}", s));
+        };
+        Ok(Self {
+            top_left,
+            top_right,
+            bottom_left,
+            bottom_right,
+            horizontal,
+            vertical,
+        })
+    }
+}
+
+impl TryFrom<String> for BorderGlyphs {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl Theme {
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let Ok(xml) = fs::read_to_string(path.as_ref()) else {
+            return Self::default();
+        };
+
+        match quick_xml::de::from_str(&xml) {
+            Ok(theme) => theme,
+            Err(e) => {
+                println!("⚠️ OSIRIS: THEME PARSE ERROR: {}. USING DEFAULT THEME.", e);
+                Self::default()
+            }
+        }
+    }
+}
+
+/// `#RRGGBB` or `#RRGGBBAA`.
+mod hex_color {
+    use serde::{Deserialize, Deserializer, de::Error};
+    use tiny_skia::Color;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let hex = s.trim().trim_start_matches('#');
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .ok_or_else(|| D::Error::custom(format!("invalid color {:?}", s)))
+        };
+
+        let alpha = match hex.len() {
+            6 => 255,
+            8 => channel(6)?,
+            _ => return Err(D::Error::custom(format!("invalid color {:?}", s))),
+        };
+        Ok(Color::from_rgba8(
+            channel(0)?,
+            channel(2)?,
+            channel(4)?,
+            alpha,
+        ))
+    }
+}
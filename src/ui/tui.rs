@@ -1,28 +1,135 @@
+use crate::commands::NavigationCommand;
+use crate::ui::theme::Theme;
 use ab_glyph::{Font, FontRef, PxScale, ScaleFont, point};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use tiny_skia::{Color, Paint, PixmapMut, Rect, Transform};
 
-pub const GRID_ROWS: usize = 45;
+pub const DEFAULT_GRID_ROWS: usize = 45;
+/// Below this the panels no longer fit on screen.
+pub const MIN_GRID_ROWS: usize = 20;
+/// Glyphs take up this share of a cell's height.
+const FONT_TO_CELL: f32 = 0.90;
+
+/// How the screen is divided into text cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GridSize {
+    /// A fixed number of rows; the text scales with the screen.
+    Rows(usize),
+    /// A fixed font size in pixels; the number of rows follows the screen.
+    FontSize(f32),
+}
+
+/// Clockwise rotation of the whole UI on the screen, for monitors mounted
+/// on their side (TATE) or upside down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u16")]
+pub enum Rotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+impl TryFrom<u16> for Rotation {
+    type Error = String;
+
+    fn try_from(degrees: u16) -> Result<Self, Self::Error> {
+        match degrees {
+            0 => Ok(Rotation::None),
+            90 => Ok(Rotation::Cw90),
+            180 => Ok(Rotation::Cw180),
+            270 => Ok(Rotation::Cw270),
+            _ => Err(format!(
+                "rotation must be 0, 90, 180 or 270, got {}",
+                degrees
+            )),
+        }
+    }
+}
+
+impl Rotation {
+    /// Size of the UI drawn onto a `width` x `height` screen.
+    pub fn apply_size(self, width: u32, height: u32) -> (u32, u32) {
+        match self {
+            Rotation::Cw90 | Rotation::Cw270 => (height, width),
+            Rotation::None | Rotation::Cw180 => (width, height),
+        }
+    }
+
+    /// Maps a direction pressed on controls that turn with the screen (the
+    /// d-pad of a handheld, say) to the direction it points in the UI.
+    pub fn remap(self, nav: NavigationCommand) -> NavigationCommand {
+        use NavigationCommand::*;
+        match (self, nav) {
+            (Rotation::None, nav) | (_, nav @ None) => nav,
+            (Rotation::Cw90, Up) => Left,
+            (Rotation::Cw90, Right) => Up,
+            (Rotation::Cw90, Down) => Right,
+            (Rotation::Cw90, Left) => Down,
+            (Rotation::Cw180, Up) => Down,
+            (Rotation::Cw180, Right) => Left,
+            (Rotation::Cw180, Down) => Up,
+            (Rotation::Cw180, Left) => Right,
+            (Rotation::Cw270, Up) => Right,
+            (Rotation::Cw270, Right) => Down,
+            (Rotation::Cw270, Down) => Left,
+            (Rotation::Cw270, Left) => Up,
+        }
+    }
+}
 
 pub struct TuiMetrics {
     pub char_width: f32,
     pub char_height: f32,
     pub font_size: f32,
     pub cols: usize,
+    pub rows: usize,
 }
 
 pub struct TuiEngine {
     pub font: FontRef<'static>,
+    pub theme: Theme,
+    pub grid: GridSize,
+    glyph_cache: RefCell<HashMap<(char, u32), Option<GlyphMask>>>,
+}
+
+/// Glyphs kept rasterized; a screen of text uses a few hundred at most.
+const GLYPH_CACHE_LIMIT: usize = 4096;
+
+/// Coverage of one rasterized glyph, placed relative to the pen position on
+/// the baseline.
+struct GlyphMask {
+    left: i32,
+    top: i32,
+    width: usize,
+    coverage: Vec<u8>, // row-major, 0..=255
 }
 
 impl TuiEngine {
-    pub fn new(font_data: &'static [u8]) -> Self {
+    pub fn new(font_data: &'static [u8], theme: Theme, grid: GridSize) -> Self {
         let font = FontRef::try_from_slice(font_data).expect("Failed to parse font");
-        Self { font }
+        Self {
+            font,
+            theme,
+            grid,
+            glyph_cache: RefCell::new(HashMap::new()),
+        }
     }
 
-    pub fn calculate_metrics(&self, width: u32, height: u32) -> TuiMetrics {
-        let char_height = height as f32 / GRID_ROWS as f32;
-        let font_size = char_height * 0.90;
+    /// Metrics for a `width` x `height` screen showing the UI rotated by
+    /// `rotation`.
+    pub fn calculate_metrics(&self, width: u32, height: u32, rotation: Rotation) -> TuiMetrics {
+        let (width, height) = rotation.apply_size(width, height);
+        let rows = match self.grid {
+            GridSize::Rows(rows) => rows,
+            GridSize::FontSize(px) => (height as f32 * FONT_TO_CELL / px.max(1.0)) as usize,
+        };
+        let rows = rows.max(MIN_GRID_ROWS);
+        let char_height = height as f32 / rows as f32;
+        let font_size = char_height * FONT_TO_CELL;
         let scale = PxScale::from(font_size);
         let scaled_font = self.font.as_scaled(scale);
         let char_width = scaled_font.h_advance(self.font.glyph_id('M'));
@@ -33,6 +140,7 @@ impl TuiEngine {
             char_height,
             font_size,
             cols,
+            rows,
         }
     }
 
@@ -48,9 +156,10 @@ impl TuiEngine {
         self.draw_string_ex(pixmap, metrics, text, col, row, color, None, 1);
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn draw_string_ex(
         &self,
-        pixmap: &mut PixmapMut, // Changed to PixmapMut for Zero-Copy
+        pixmap: &mut PixmapMut,
         metrics: &TuiMetrics,
         text: &str,
         col: usize,
@@ -60,7 +169,6 @@ impl TuiEngine {
         scale_factor: usize,
     ) {
         let scaled_font_size = metrics.font_size * scale_factor as f32;
-        let scale = PxScale::from(scaled_font_size);
         let char_w = metrics.char_width * scale_factor as f32;
         let char_h = metrics.char_height * scale_factor as f32;
         let x_start = col as f32 * metrics.char_width;
@@ -79,6 +187,22 @@ impl TuiEngine {
         }
 
         // 2. DRAW GLYPHS (Direct Pixel Access)
+        let glyphs = text.chars().enumerate().map(|(i, c)| {
+            let pen_x = x_start + i as f32 * char_w;
+            (c, pen_x, y_baseline)
+        });
+        self.draw_glyphs(pixmap, glyphs, scaled_font_size, color);
+    }
+
+    /// Blits glyphs (char, pen x, baseline y) from the coverage cache,
+    /// rasterizing the ones not seen at this size yet.
+    fn draw_glyphs(
+        &self,
+        pixmap: &mut PixmapMut,
+        glyphs: impl Iterator<Item = (char, f32, f32)>,
+        font_size: f32,
+        color: Color,
+    ) {
         // This is 50x faster than calling fill_rect for every pixel of text
         let pix_w = pixmap.width() as i32;
         let pix_h = pixmap.height() as i32;
@@ -87,71 +211,101 @@ impl TuiEngine {
         let g = (color.green() * 255.0) as u8;
         let b = (color.blue() * 255.0) as u8;
 
-        let mut x_cursor = x_start;
-
         // Start mutable borrow of raw pixels
         let pixels = pixmap.pixels_mut();
+        let mut cache = self.glyph_cache.borrow_mut();
 
-        for c in text.chars() {
-            let glyph = self
-                .font
-                .glyph_id(c)
-                .with_scale_and_position(scale, point(x_cursor, y_baseline));
+        for (c, pen_x, pen_y) in glyphs {
+            let key = (c, font_size.to_bits());
+            if !cache.contains_key(&key) && cache.len() >= GLYPH_CACHE_LIMIT {
+                // Font size changed (resize) or a huge charset; start over
+                cache.clear();
+            }
+            let Some(mask) = cache
+                .entry(key)
+                .or_insert_with(|| self.rasterize(c, font_size))
+            else {
+                continue;
+            };
 
-            if let Some(outlined) = self.font.outline_glyph(glyph) {
-                let bounds = outlined.px_bounds();
-                outlined.draw(|x, y, coverage| {
+            let left = pen_x.round() as i32 + mask.left;
+            let top = pen_y.round() as i32 + mask.top;
+            for (y, line) in mask.coverage.chunks_exact(mask.width).enumerate() {
+                let py = top + y as i32;
+                if py < 0 || py >= pix_h {
+                    continue;
+                }
+                for (x, &alpha) in line.iter().enumerate() {
+                    let px = left + x as i32;
                     // Only draw significant pixels to keep text crisp
-                    if coverage > 0.2 {
-                        let px = bounds.min.x as i32 + x as i32;
-                        let py = bounds.min.y as i32 + y as i32;
-
-                        if px >= 0 && px < pix_w && py >= 0 && py < pix_h {
-                            let idx = (py * pix_w + px) as usize;
-                            let alpha = (coverage * 255.0) as u8;
-
-                            // Simple Integer Blending (Source Over)
-                            // This reads the current pixel (likely the background we just drew)
-                            // and blends the text color on top.
-                            let dest = &mut pixels[idx];
-
-                            if alpha == 255 {
-                                // Solid optimization
-                                if let Some(p) =
-                                    tiny_skia::PremultipliedColorU8::from_rgba(r, g, b, 255)
-                                {
-                                    *dest = p;
-                                }
-                            } else {
-                                // Alpha blending logic
-                                let a = alpha as u32;
-                                let inv_a = 255 - a;
-
-                                let out_r =
-                                    ((r as u32 * a + dest.red() as u32 * inv_a) / 255) as u8;
-                                let out_g =
-                                    ((g as u32 * a + dest.green() as u32 * inv_a) / 255) as u8;
-                                let out_b =
-                                    ((b as u32 * a + dest.blue() as u32 * inv_a) / 255) as u8;
-                                let out_a = ((255 * a + dest.alpha() as u32 * inv_a) / 255) as u8;
-
-                                if let Some(blended) = tiny_skia::PremultipliedColorU8::from_rgba(
-                                    out_r, out_g, out_b, out_a,
-                                ) {
-                                    *dest = blended;
-                                }
-                            }
+                    if alpha <= 51 || px < 0 || px >= pix_w {
+                        continue;
+                    }
+
+                    // Simple Integer Blending (Source Over)
+                    // This reads the current pixel (likely the background we just drew)
+                    // and blends the text color on top.
+                    let dest = &mut pixels[(py * pix_w + px) as usize];
+
+                    if alpha == 255 {
+                        // Solid optimization
+                        if let Some(p) = tiny_skia::PremultipliedColorU8::from_rgba(r, g, b, 255) {
+                            *dest = p;
+                        }
+                    } else {
+                        // Alpha blending logic
+                        let a = alpha as u32;
+                        let inv_a = 255 - a;
+
+                        let out_r = ((r as u32 * a + dest.red() as u32 * inv_a) / 255) as u8;
+                        let out_g = ((g as u32 * a + dest.green() as u32 * inv_a) / 255) as u8;
+                        let out_b = ((b as u32 * a + dest.blue() as u32 * inv_a) / 255) as u8;
+                        let out_a = ((255 * a + dest.alpha() as u32 * inv_a) / 255) as u8;
+
+                        if let Some(blended) =
+                            tiny_skia::PremultipliedColorU8::from_rgba(out_r, out_g, out_b, out_a)
+                        {
+                            *dest = blended;
                         }
                     }
-                });
+                }
             }
-            x_cursor += char_w;
         }
     }
 
+    /// Coverage mask of `c` with the pen at the origin. None for glyphs
+    /// without an outline (spaces).
+    fn rasterize(&self, c: char, font_size: f32) -> Option<GlyphMask> {
+        let glyph = self
+            .font
+            .glyph_id(c)
+            .with_scale_and_position(PxScale::from(font_size), point(0.0, 0.0));
+        let outlined = self.font.outline_glyph(glyph)?;
+        let bounds = outlined.px_bounds();
+        let width = bounds.width() as usize;
+        let height = bounds.height() as usize;
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        let mut coverage = vec![0; width * height];
+        outlined.draw(|x, y, c| {
+            if let Some(px) = coverage.get_mut(y as usize * width + x as usize) {
+                *px = (c * 255.0) as u8;
+            }
+        });
+        Some(GlyphMask {
+            left: bounds.min.x as i32,
+            top: bounds.min.y as i32,
+            width,
+            coverage,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn draw_box(
         &self,
-        pixmap: &mut PixmapMut, // Changed to PixmapMut
+        pixmap: &mut PixmapMut,
         metrics: &TuiMetrics,
         x: usize,
         y: usize,
@@ -162,19 +316,31 @@ impl TuiEngine {
         if w < 2 || h < 2 {
             return;
         }
-        // We reuse draw_string which now handles PixmapMut correctly
-        self.draw_string(pixmap, metrics, "┌", x, y, color);
-        self.draw_string(pixmap, metrics, "┐", x + w - 1, y, color);
-        self.draw_string(pixmap, metrics, "└", x, y + h - 1, color);
-        self.draw_string(pixmap, metrics, "┘", x + w - 1, y + h - 1, color);
-
-        let h_line = "─".repeat(w.saturating_sub(2));
-        self.draw_string(pixmap, metrics, &h_line, x + 1, y, color);
-        self.draw_string(pixmap, metrics, &h_line, x + 1, y + h - 1, color);
-
-        for i in 1..h.saturating_sub(1) {
-            self.draw_string(pixmap, metrics, "│", x, y + i, color);
-            self.draw_string(pixmap, metrics, "│", x + w - 1, y + i, color);
+        let border = self.theme.border;
+        let (right, bottom) = (x + w - 1, y + h - 1);
+
+        // Every border cell in one pass over the glyph cache
+        let mut cells = vec![
+            (border.top_left, x, y),
+            (border.top_right, right, y),
+            (border.bottom_left, x, bottom),
+            (border.bottom_right, right, bottom),
+        ];
+        for col in x + 1..right {
+            cells.push((border.horizontal, col, y));
+            cells.push((border.horizontal, col, bottom));
         }
+        for row in y + 1..bottom {
+            cells.push((border.vertical, x, row));
+            cells.push((border.vertical, right, row));
+        }
+
+        let baseline = metrics.char_height * 0.82;
+        let glyphs = cells.into_iter().map(|(c, col, row)| {
+            let pen_x = col as f32 * metrics.char_width;
+            let pen_y = row as f32 * metrics.char_height + baseline;
+            (c, pen_x, pen_y)
+        });
+        self.draw_glyphs(pixmap, glyphs, metrics.font_size, color);
     }
 }
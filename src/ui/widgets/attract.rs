@@ -0,0 +1,312 @@
+use crate::commands::{ControlCommand, UiEvent};
+use crate::models::{Cabinet, SharedLibrary};
+use crate::tui::{TuiEngine, TuiMetrics};
+use crate::ui::images::{self, ImageState, SharedImages};
+use crate::ui::widgets::common::{CellRect, Modal, Widget};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tiny_skia::{Color, Paint, PixmapMut, Rect, Transform};
+
+/// Full-screen slideshow of random games shown while the cabinet sits idle.
+/// Opened by `UiEvent::Idle`; any input closes it and is otherwise ignored,
+/// so the selection underneath is left exactly as it was.
+pub struct AttractWidget {
+    library: SharedLibrary,
+    images: SharedImages,
+    active: bool,
+    slide_duration: Duration,
+    dim: bool,
+    cabinet: Option<Cabinet>,
+    playlist: Vec<(usize, usize)>,
+    slide: usize,
+    slide_start: Instant,
+    frame_due: Option<Instant>,
+    dirty: bool,
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+}
+
+impl AttractWidget {
+    pub fn new(
+        library: SharedLibrary,
+        images: SharedImages,
+        slide_duration: Duration,
+        dim: bool,
+        cabinet: Option<Cabinet>,
+    ) -> Self {
+        Self {
+            library,
+            images,
+            active: false,
+            slide_duration,
+            dim,
+            cabinet,
+            playlist: Vec::new(),
+            slide: 0,
+            slide_start: Instant::now(),
+            frame_due: None,
+            dirty: true,
+            x: 0,
+            y: 0,
+            w: 0,
+            h: 0,
+        }
+    }
+
+    /// Shuffles every game with media (or every game, if none has any) that
+    /// the cabinet can play.
+    fn start(&mut self) {
+        let library = self.library.borrow();
+        let all: Vec<(usize, usize)> = library
+            .systems
+            .iter()
+            .enumerate()
+            .flat_map(|(s, system)| (0..system.games.len()).map(move |g| (s, g)))
+            .filter(|&(s, g)| {
+                let game = &library.systems[s].games[g];
+                self.cabinet.as_ref().is_none_or(|c| c.can_play(game))
+            })
+            .collect();
+        let with_media: Vec<(usize, usize)> = all
+            .iter()
+            .copied()
+            .filter(|&(s, g)| library.systems[s].games[g].media.primary_image().is_some())
+            .collect();
+        self.playlist = if with_media.is_empty() {
+            all
+        } else {
+            with_media
+        };
+        drop(library);
+
+        shuffle(&mut self.playlist);
+        self.active = !self.playlist.is_empty();
+        self.show_slide(0, Instant::now());
+    }
+
+    fn show_slide(&mut self, slide: usize, now: Instant) {
+        self.slide = slide;
+        self.slide_start = now;
+        self.dirty = true;
+
+        // Fetch this slide's image and the next one ahead of time
+        let paths: Vec<PathBuf> = [slide, slide + 1]
+            .iter()
+            .filter_map(|&i| self.image_path(i))
+            .collect();
+        let mut images = self.images.borrow_mut();
+        for path in &paths {
+            images.request(path);
+        }
+    }
+
+    fn image_path(&self, slide: usize) -> Option<PathBuf> {
+        let &(s, g) = self.playlist.get(slide % self.playlist.len().max(1))?;
+        let library = self.library.borrow();
+        library.systems[s].games[g].media.primary_image().cloned()
+    }
+}
+
+/// Fisher-Yates with a xorshift generator seeded from the clock; good enough
+/// for picking slides and saves pulling in a random number crate.
+fn shuffle<T>(items: &mut [T]) {
+    let mut seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0x9E37_79B9, |d| d.as_nanos() as u64)
+        | 1;
+    for i in (1..items.len()).rev() {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        items.swap(i, (seed % (i as u64 + 1)) as usize);
+    }
+}
+
+impl Modal for AttractWidget {
+    fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+impl Widget for AttractWidget {
+    fn draw(&self, pixmap: &mut PixmapMut, engine: &TuiEngine, metrics: &TuiMetrics) {
+        let palette = &engine.theme.palette;
+
+        let Some(&(s, g)) = self.playlist.get(self.slide % self.playlist.len().max(1)) else {
+            return;
+        };
+        let library = self.library.borrow();
+        let system = &library.systems[s];
+        let game = &system.games[g];
+
+        for row in 0..self.h {
+            engine.draw_string_ex(
+                pixmap,
+                metrics,
+                &" ".repeat(self.w),
+                self.x,
+                self.y + row,
+                Color::TRANSPARENT,
+                Some(palette.background),
+                1,
+            );
+        }
+
+        // Media fills everything above the caption
+        let caption_h = 6;
+        let img_h = self.h.saturating_sub(caption_h + 1);
+        let images = self.images.borrow();
+        let image = game
+            .media
+            .primary_image()
+            .map_or(ImageState::Missing, |p| images.get(p));
+        if let ImageState::Ready(image) = image {
+            let (frame, _) = image.frame_at(Instant::now().duration_since(self.slide_start));
+            images::draw_fit(
+                pixmap,
+                frame,
+                (self.x + 1) as f32 * metrics.char_width,
+                (self.y + 1) as f32 * metrics.char_height,
+                self.w.saturating_sub(2) as f32 * metrics.char_width,
+                img_h as f32 * metrics.char_height,
+            );
+        }
+
+        // Caption: title at double size, then the system and release info
+        let caption_y = self.y + self.h.saturating_sub(caption_h);
+        let center = |text: &str, scale: usize| {
+            self.x + (self.w / 2).saturating_sub(text.chars().count() * scale / 2)
+        };
+        engine.draw_string_ex(
+            pixmap,
+            metrics,
+            &game.name,
+            center(&game.name, 2),
+            caption_y,
+            palette.text,
+            None,
+            2,
+        );
+        let info = match (game.release_date, &game.developer) {
+            (Some(date), Some(developer)) => {
+                format!("{} | {} | {}", system.name, date.year, developer)
+            }
+            (Some(date), None) => format!("{} | {}", system.name, date.year),
+            (None, Some(developer)) => format!("{} | {}", system.name, developer),
+            (None, None) => system.name.clone(),
+        };
+        engine.draw_string(
+            pixmap,
+            metrics,
+            &info,
+            center(&info, 1),
+            caption_y + 3,
+            palette.accent,
+        );
+
+        let hint = "PRESS ANY BUTTON";
+        engine.draw_string(
+            pixmap,
+            metrics,
+            hint,
+            center(hint, 1),
+            caption_y + 5,
+            palette.muted,
+        );
+        engine.draw_box(
+            pixmap,
+            metrics,
+            self.x,
+            self.y,
+            self.w,
+            self.h,
+            palette.frame,
+        );
+
+        // Dim mode keeps the slideshow running at low brightness
+        if self.dim
+            && let Some(rect) = Rect::from_xywh(
+                self.x as f32 * metrics.char_width,
+                self.y as f32 * metrics.char_height,
+                self.w as f32 * metrics.char_width,
+                self.h as f32 * metrics.char_height,
+            )
+        {
+            let mut paint = Paint::default();
+            paint.set_color(palette.dim);
+            pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+        }
+    }
+
+    fn set_rect(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        self.dirty |= CellRect::new(x, y, w, h) != self.rect();
+        self.x = x;
+        self.y = y;
+        self.w = w;
+        self.h = h;
+    }
+
+    fn rect(&self) -> CellRect {
+        CellRect::new(self.x, self.y, self.w, self.h)
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn damage(&mut self, out: &mut Vec<CellRect>) {
+        if std::mem::take(&mut self.dirty) {
+            out.push(self.rect());
+        }
+    }
+
+    fn handle_command(&mut self, _cmd: ControlCommand) -> UiEvent {
+        // Any input just wakes the cabinet up
+        self.active = false;
+        self.dirty = true;
+        UiEvent::None
+    }
+
+    fn handle_ui_event(&mut self, event: UiEvent) -> UiEvent {
+        match event {
+            UiEvent::Idle if !self.active => self.start(),
+            UiEvent::ImagesLoaded if self.active => self.dirty = true,
+            // The playlist points into the old library
+            UiEvent::LibraryReplaced(_) if self.active => {
+                self.dirty = true;
+                self.start();
+            }
+            UiEvent::LibraryReplaced(_) => self.playlist.clear(),
+            _ => {}
+        }
+        UiEvent::None
+    }
+
+    fn next_frame(&mut self, now: Instant) -> Option<Instant> {
+        if !self.active {
+            return None;
+        }
+
+        let mut slide_end = self.slide_start + self.slide_duration;
+        if now >= slide_end {
+            self.show_slide(self.slide + 1, now);
+            slide_end = now + self.slide_duration;
+        }
+
+        // Animated media play until the slide changes
+        self.dirty |= self.frame_due.is_some_and(|due| due <= now);
+        let animation =
+            self.image_path(self.slide)
+                .and_then(|path| match self.images.borrow().get(&path) {
+                    ImageState::Ready(image) => image
+                        .frame_at(now.duration_since(self.slide_start))
+                        .1
+                        .map(|remaining| now + remaining),
+                    _ => None,
+                });
+        self.frame_due = animation;
+        Some(animation.map_or(slide_end, |frame| frame.min(slide_end)))
+    }
+}
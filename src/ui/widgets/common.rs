@@ -1,15 +1,117 @@
 use crate::commands::{ControlCommand, UiEvent};
+use std::time::Instant;
 use tiny_skia::PixmapMut;
 
 use crate::ui::tui::{TuiEngine, TuiMetrics};
 
+/// A rectangle of grid cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellRect {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+impl CellRect {
+    pub fn new(x: usize, y: usize, w: usize, h: usize) -> Self {
+        Self { x, y, w, h }
+    }
+
+    pub fn intersects(&self, other: &CellRect) -> bool {
+        self.x < other.x + other.w
+            && other.x < self.x + self.w
+            && self.y < other.y + other.h
+            && other.y < self.y + self.h
+    }
+}
+
 pub(crate) trait Widget {
     fn draw(&self, pixmap: &mut PixmapMut, engine: &TuiEngine, metrics: &TuiMetrics);
     fn set_rect(&mut self, x: usize, y: usize, w: usize, h: usize);
+    /// The rect last given to `set_rect`.
+    fn rect(&self) -> CellRect;
+    /// Whether the widget changed since it was last painted.
+    fn is_dirty(&self) -> bool;
+    /// Collects the rects that need repainting and clears the dirty flags.
+    fn damage(&mut self, out: &mut Vec<CellRect>);
+    /// Paints the widget if it overlaps any of `damage`. Containers pass the
+    /// damage down so only the overlapping children are painted.
+    fn draw_damaged(
+        &self,
+        pixmap: &mut PixmapMut,
+        engine: &TuiEngine,
+        metrics: &TuiMetrics,
+        damage: &[CellRect],
+    ) {
+        let rect = self.rect();
+        if damage.iter().any(|d| d.intersects(&rect)) {
+            self.draw(pixmap, engine, metrics);
+        }
+    }
     fn handle_command(&mut self, cmd: ControlCommand) -> UiEvent;
-    fn handle_ui_event(&mut self, _event: UiEvent);
+    /// Reacts to an event raised elsewhere in the tree. The returned event (if
+    /// any) is dispatched to the whole tree in turn.
+    fn handle_ui_event(&mut self, _event: UiEvent) -> UiEvent;
+    /// Advances time-driven state to `now` and returns when the widget will
+    /// next look different without any input (the next frame of an animation,
+    /// the next slide), so the event loop can sleep until then.
+    fn next_frame(&mut self, _now: Instant) -> Option<Instant> {
+        None
+    }
+}
+
+/// Lets trees built at runtime (see `ui::layout`) nest in the generic
+/// containers.
+impl<W: Widget + ?Sized> Widget for Box<W> {
+    fn draw(&self, pixmap: &mut PixmapMut, engine: &TuiEngine, metrics: &TuiMetrics) {
+        (**self).draw(pixmap, engine, metrics);
+    }
+
+    fn set_rect(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        (**self).set_rect(x, y, w, h);
+    }
+
+    fn rect(&self) -> CellRect {
+        (**self).rect()
+    }
+
+    fn is_dirty(&self) -> bool {
+        (**self).is_dirty()
+    }
+
+    fn damage(&mut self, out: &mut Vec<CellRect>) {
+        (**self).damage(out);
+    }
+
+    fn draw_damaged(
+        &self,
+        pixmap: &mut PixmapMut,
+        engine: &TuiEngine,
+        metrics: &TuiMetrics,
+        damage: &[CellRect],
+    ) {
+        (**self).draw_damaged(pixmap, engine, metrics, damage);
+    }
+
+    fn handle_command(&mut self, cmd: ControlCommand) -> UiEvent {
+        (**self).handle_command(cmd)
+    }
+
+    fn handle_ui_event(&mut self, event: UiEvent) -> UiEvent {
+        (**self).handle_ui_event(event)
+    }
+
+    fn next_frame(&mut self, now: Instant) -> Option<Instant> {
+        (**self).next_frame(now)
+    }
 }
 
 pub(crate) trait Container {
     fn arrange_widgets(&mut self);
 }
+
+/// A widget drawn over the layout that takes all input while active.
+pub(crate) trait Modal {
+    fn is_active(&self) -> bool;
+}
@@ -0,0 +1,294 @@
+use crate::commands::{ActionCommand, ControlCommand, NavigationCommand, UiEvent};
+use crate::models::{GameFilter, SharedLibrary};
+use crate::tui::{TuiEngine, TuiMetrics};
+use crate::ui::widgets::common::{CellRect, Modal, Widget};
+use tiny_skia::{Color, PixmapMut};
+
+const ROW_LABELS: [&str; 7] = [
+    "GENRE",
+    "DECADE",
+    "PLAYERS",
+    "FAVORITES",
+    "WORKING",
+    "MATURE",
+    "",
+];
+const CLEAR_ROW: usize = 6;
+const PANEL_W: usize = 44;
+
+/// Modal panel editing the current system's `GameFilter`. Up/Down picks a
+/// row, Left/Right changes its value, Select on "CLEAR" resets everything.
+pub struct FilterPanelWidget {
+    library: SharedLibrary,
+    selected_system: usize,
+    active: bool,
+    cursor: usize,
+    dirty: bool,
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+}
+
+impl FilterPanelWidget {
+    pub fn new(library: SharedLibrary) -> Self {
+        Self {
+            library,
+            selected_system: 0,
+            active: false,
+            cursor: 0,
+            dirty: true,
+            x: 0,
+            y: 0,
+            w: 0,
+            h: 0,
+        }
+    }
+
+    fn current_filter(&self) -> GameFilter {
+        self.library
+            .borrow()
+            .systems
+            .get(self.selected_system)
+            .map(|s| s.filter.clone())
+            .unwrap_or_default()
+    }
+
+    /// Applies `change` to the system's filter. Returns true if it changed.
+    fn update_filter(&mut self, change: impl FnOnce(&mut GameFilter)) -> bool {
+        let mut library = self.library.borrow_mut();
+        let Some(system) = library.systems.get_mut(self.selected_system) else {
+            return false;
+        };
+        let old = system.filter.clone();
+        change(&mut system.filter);
+        system.filter != old
+    }
+
+    fn step_value(&mut self, forward: bool) -> bool {
+        let (genres, decades, max_players) = {
+            let library = self.library.borrow();
+            let Some(system) = library.systems.get(self.selected_system) else {
+                return false;
+            };
+
+            let mut genres: Vec<String> = system
+                .games
+                .iter()
+                .flat_map(|g| g.genres.iter().cloned())
+                .collect();
+            genres.sort_by_key(|g| g.to_lowercase());
+            genres.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+
+            let mut decades: Vec<u16> = system
+                .games
+                .iter()
+                .filter_map(|g| g.release_date.map(|d| d.year / 10 * 10))
+                .collect();
+            decades.sort_unstable();
+            decades.dedup();
+
+            let max_players = system.games.iter().map(|g| g.max_players).max();
+            (genres, decades, max_players.unwrap_or(1))
+        };
+        let players: Vec<u8> = (2..=max_players).collect();
+
+        match self.cursor {
+            0 => self.update_filter(|f| f.genre = cycle(&f.genre, &genres, forward)),
+            1 => self.update_filter(|f| f.decade = cycle(&f.decade, &decades, forward)),
+            2 => self.update_filter(|f| f.players = cycle(&f.players, &players, forward)),
+            3 => self.update_filter(|f| f.favorites_only = !f.favorites_only),
+            4 => self.update_filter(|f| f.working_only = !f.working_only),
+            5 => self.update_filter(|f| f.hide_mature = !f.hide_mature),
+            _ => false,
+        }
+    }
+
+    fn row_value(filter: &GameFilter, row: usize) -> String {
+        let only = |on: bool| if on { "ONLY" } else { "ALL" }.to_string();
+        match row {
+            0 => filter.genre.clone().unwrap_or("ALL".into()),
+            1 => filter.decade.map_or("ALL".into(), |d| format!("{}s", d)),
+            2 => filter.players.map_or("ANY".into(), |p| format!("{}+", p)),
+            3 => only(filter.favorites_only),
+            4 => only(filter.working_only),
+            5 => if filter.hide_mature {
+                "HIDDEN"
+            } else {
+                "SHOWN"
+            }
+            .to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Steps through `None` (no filtering) followed by every value in `values`.
+fn cycle<T: PartialEq + Clone>(current: &Option<T>, values: &[T], forward: bool) -> Option<T> {
+    let pos = current
+        .as_ref()
+        .and_then(|c| values.iter().position(|v| v == c));
+    let next = match (pos, forward) {
+        (None, true) => Some(0),
+        (None, false) => values.len().checked_sub(1),
+        (Some(i), true) => (i + 1 < values.len()).then_some(i + 1),
+        (Some(i), false) => i.checked_sub(1),
+    };
+    next.and_then(|i| values.get(i).cloned())
+}
+
+impl Modal for FilterPanelWidget {
+    fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+impl Widget for FilterPanelWidget {
+    fn draw(&self, pixmap: &mut PixmapMut, engine: &TuiEngine, metrics: &TuiMetrics) {
+        let palette = &engine.theme.palette;
+
+        let box_w = PANEL_W.min(self.w);
+        let box_h = ROW_LABELS.len() + 4;
+        let box_x = self.x + self.w.saturating_sub(box_w) / 2;
+        let box_y = self.y + self.h.saturating_sub(box_h) / 2;
+
+        // Solid backdrop so the list underneath doesn't bleed through
+        for row in 0..box_h {
+            engine.draw_string_ex(
+                pixmap,
+                metrics,
+                &" ".repeat(box_w),
+                box_x,
+                box_y + row,
+                Color::TRANSPARENT,
+                Some(palette.panel_bg),
+                1,
+            );
+        }
+        engine.draw_box(pixmap, metrics, box_x, box_y, box_w, box_h, palette.frame);
+        engine.draw_string_ex(
+            pixmap,
+            metrics,
+            " FILTERS ",
+            box_x + 2,
+            box_y,
+            palette.accent,
+            Some(palette.panel_bg),
+            1,
+        );
+
+        let filter = self.current_filter();
+        let text_w = box_w.saturating_sub(4);
+        for (row, label) in ROW_LABELS.iter().enumerate() {
+            let text = if row == CLEAR_ROW {
+                format!("{:^width$}", "[ CLEAR ALL ]", width = text_w)
+            } else {
+                let value = format!("◄ {} ►", Self::row_value(&filter, row));
+                format!(
+                    "{:<12}{:>width$}",
+                    label,
+                    value,
+                    width = text_w.saturating_sub(12)
+                )
+            };
+            let text: String = text.chars().take(text_w).collect();
+
+            if row == self.cursor {
+                engine.draw_string_ex(
+                    pixmap,
+                    metrics,
+                    &text,
+                    box_x + 2,
+                    box_y + 2 + row,
+                    palette.text,
+                    Some(palette.highlight_bg),
+                    1,
+                );
+            } else {
+                engine.draw_string(
+                    pixmap,
+                    metrics,
+                    &text,
+                    box_x + 2,
+                    box_y + 2 + row,
+                    palette.text_dim,
+                );
+            }
+        }
+    }
+
+    fn set_rect(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        self.dirty |= CellRect::new(x, y, w, h) != self.rect();
+        self.x = x;
+        self.y = y;
+        self.w = w;
+        self.h = h;
+    }
+
+    fn rect(&self) -> CellRect {
+        CellRect::new(self.x, self.y, self.w, self.h)
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn damage(&mut self, out: &mut Vec<CellRect>) {
+        if std::mem::take(&mut self.dirty) {
+            out.push(self.rect());
+        }
+    }
+
+    fn handle_command(&mut self, cmd: ControlCommand) -> UiEvent {
+        // Opening, closing or any input while open changes the panel
+        self.dirty |= self.active;
+        if !self.active {
+            if cmd == ControlCommand::Action(ActionCommand::OpenFilters) {
+                self.active = true;
+                self.dirty = true;
+                self.cursor = 0;
+            }
+            return UiEvent::None;
+        }
+
+        let changed = match cmd {
+            ControlCommand::Navigation(NavigationCommand::Up) => {
+                self.cursor = self.cursor.saturating_sub(1);
+                false
+            }
+            ControlCommand::Navigation(NavigationCommand::Down) => {
+                self.cursor = (self.cursor + 1).min(CLEAR_ROW);
+                false
+            }
+            ControlCommand::Navigation(NavigationCommand::Left) => self.step_value(false),
+            ControlCommand::Navigation(NavigationCommand::Right) => self.step_value(true),
+            ControlCommand::Action(ActionCommand::Select) if self.cursor == CLEAR_ROW => {
+                self.update_filter(|f| *f = GameFilter::default())
+            }
+            ControlCommand::Action(ActionCommand::Select) => self.step_value(true),
+            ControlCommand::Action(ActionCommand::Back | ActionCommand::OpenFilters) => {
+                self.active = false;
+                false
+            }
+            _ => false,
+        };
+
+        if changed {
+            UiEvent::UserStateChanged
+        } else {
+            UiEvent::None
+        }
+    }
+
+    fn handle_ui_event(&mut self, event: UiEvent) -> UiEvent {
+        match event {
+            UiEvent::SystemChanged(system_idx)
+            | UiEvent::JumpToGame(system_idx, _)
+            | UiEvent::LibraryReplaced(system_idx) => {
+                self.selected_system = system_idx;
+            }
+            _ => {}
+        }
+        UiEvent::None
+    }
+}
@@ -1,18 +1,38 @@
 use crate::{
     commands::{ActionCommand, ControlCommand, UiEvent},
-    models::RomLibrary,
+    models::{Control, ControlKind, Game, SharedLibrary},
     tui::{TuiEngine, TuiMetrics},
-    ui::widgets::common::Widget,
+    ui::images::{self, Image, ImageState, SharedImages},
+    ui::widgets::common::{CellRect, Widget},
 };
-use image::GenericImageView;
-use std::{path::PathBuf, rc::Rc};
-use tiny_skia::{Color, Pixmap, PixmapMut};
+use std::path::PathBuf;
+use std::time::Instant;
+use tiny_skia::{Color, PixmapMut};
+
+/// Rows of platform, file, id and year/developer/players info.
+const INFO_LINES: usize = 4;
+/// Rows below the info needed to show both the images and the texts.
+const MIN_SHARED_ROWS: usize = 6;
+/// The control panel diagram and its frame.
+const CONTROLS_ROWS: usize = 5;
+
+/// Rows of the boxes below the info, as `(row, rows)`.
+struct Panes {
+    controls: Option<(usize, usize)>,
+    media: (usize, usize),
+    texts: Option<(usize, usize)>,
+}
 
 pub struct GameWidget {
-    library: Rc<RomLibrary>,
+    library: SharedLibrary,
     selected_system: usize,
-    selected_game: usize,
-    current_image: Option<Pixmap>,
+    selected_game: Option<usize>,
+    media_index: usize,
+    text_scroll: usize,      // first line shown in the text pane
+    playback_start: Instant, // animated media restart on every selection
+    images: SharedImages,
+    dirty: bool,
+    frame_due: Option<Instant>,
     pub x: usize,
     pub y: usize,
     pub w: usize,
@@ -20,12 +40,17 @@ pub struct GameWidget {
 }
 
 impl GameWidget {
-    pub fn new(library: Rc<RomLibrary>) -> Self {
+    pub fn new(library: SharedLibrary, images: SharedImages) -> Self {
         Self {
             library,
             selected_system: 0,
-            selected_game: 0,
-            current_image: None,
+            selected_game: None,
+            media_index: 0,
+            text_scroll: 0,
+            playback_start: Instant::now(),
+            images,
+            dirty: true,
+            frame_due: None,
             x: 0,
             y: 0,
             w: 0,
@@ -34,230 +59,709 @@ impl GameWidget {
     }
 
     fn load_image(&mut self) {
-        let system = &self.library.systems[self.selected_system];
-        let game = &system.games[self.selected_game];
-
-        // Path: data/[system]/[id].png
-        let img_path = PathBuf::from("roms")
-            .join(&system.name.to_lowercase())
-            .join("images")
-            .join(format!("{}-image.png", game.id));
-
-        self.current_image = if let Ok(img) = image::open(&img_path) {
-            let (width, height) = img.dimensions();
-            let mut pixmap = Pixmap::new(width, height).unwrap();
-
-            // Convert image to tiny_skia format
-            for (x, y, pixel) in img.pixels() {
-                let r = pixel[0];
-                let g = pixel[1];
-                let b = pixel[2];
-                let a = pixel[3];
-                if let Some(c) = tiny_skia::PremultipliedColorU8::from_rgba(r, g, b, a) {
-                    pixmap.pixels_mut()[(y * width + x) as usize] = c;
-                }
-            }
-            Some(pixmap)
+        self.dirty = true;
+        self.playback_start = Instant::now();
+
+        // The primary image is usually already queued by the list's prefetch
+        if let Some(img_path) = self.shown_image_path() {
+            self.images.borrow_mut().request(&img_path);
+        }
+    }
+
+    fn shown_image_path(&self) -> Option<PathBuf> {
+        let library = self.library.borrow();
+        let game = library
+            .systems
+            .get(self.selected_system)?
+            .games
+            .get(self.selected_game?)?;
+        let images = game.media.images();
+        images
+            .get(self.media_index % images.len().max(1))
+            .map(|(_, path)| path.to_path_buf())
+    }
+
+    fn shown_frame<'a>(
+        &self,
+        image: &'a Image,
+        now: Instant,
+    ) -> (&'a tiny_skia::Pixmap, Option<Instant>) {
+        let (pixmap, remaining) = image.frame_at(now.duration_since(self.playback_start));
+        (pixmap, remaining.map(|r| now + r))
+    }
+
+    /// The control panel, the media box and, when the game has texts to
+    /// show, the text pane below it. Without images the texts take it all.
+    fn panes(&self, game: &Game) -> Panes {
+        let inner_bottom = self.y + self.h.saturating_sub(1);
+        let mut top = self.y + 6 + INFO_LINES;
+        let controls =
+            (!game.controls.is_empty() && top + CONTROLS_ROWS <= inner_bottom).then(|| {
+                top += CONTROLS_ROWS;
+                (top - CONTROLS_ROWS, CONTROLS_ROWS)
+            });
+
+        let rows = inner_bottom.saturating_sub(top);
+        let media_rows = if game.media.images().is_empty() {
+            0
+        } else if rows >= MIN_SHARED_ROWS {
+            rows / 2
         } else {
-            None // File not found or corrupt
+            rows
         };
+        let texts = (has_texts(game) && rows - media_rows >= 3)
+            .then_some((top + media_rows, rows - media_rows));
+        Panes {
+            controls,
+            media: (top, if texts.is_some() { media_rows } else { rows }),
+            texts,
+        }
     }
-}
 
-impl Widget for GameWidget {
-    fn draw(&self, pixmap: &mut PixmapMut, engine: &TuiEngine, metrics: &TuiMetrics) {
-        // COLORS (BGR for Zero-Copy)
-        let cyan = Color::from_rgba8(255, 255, 0, 255);
-        let green = Color::from_rgba8(0, 255, 0, 255);
-        let white = Color::from_rgba8(255, 255, 255, 255);
-        let dark_bg = Color::from_rgba8(5, 15, 5, 255);
-        let bg_main = Color::from_rgba8(5, 10, 0, 255);
+    /// The wrapped texts of the game and how many of their lines fit the
+    /// text pane.
+    fn text_view(&self, game: &Game) -> Option<(Vec<(String, bool)>, usize)> {
+        let (_, rows) = self.panes(game).texts?;
+        Some((text_lines(game, self.w.saturating_sub(6)), rows - 2))
+    }
 
-        // 1. Draw Container Frame
-        engine.draw_box(pixmap, metrics, self.x, self.y, self.w, self.h, cyan);
-        engine.draw_string_ex(
+    fn scroll_text(&mut self, down: bool) {
+        let library = self.library.borrow();
+        let Some((lines, visible)) = library
+            .systems
+            .get(self.selected_system)
+            .zip(self.selected_game)
+            .and_then(|(s, g)| s.games.get(g))
+            .and_then(|game| self.text_view(game))
+        else {
+            return;
+        };
+
+        // A page at a time, keeping one line of the previous page in view
+        let page = visible.saturating_sub(1).max(1);
+        let last = lines.len().saturating_sub(visible);
+        let scroll = if down {
+            (self.text_scroll + page).min(last)
+        } else {
+            self.text_scroll.min(last).saturating_sub(page)
+        };
+        drop(library);
+        if scroll != self.text_scroll {
+            self.text_scroll = scroll;
+            self.dirty = true;
+        }
+    }
+
+    /// 5. Player 1's side of the control panel, next to a summary.
+    fn draw_controls(
+        &self,
+        pixmap: &mut PixmapMut,
+        engine: &TuiEngine,
+        metrics: &TuiMetrics,
+        game: &Game,
+        (panel_y, panel_h): (usize, usize),
+    ) {
+        let palette = &engine.theme.palette;
+        let panel_w = self.w.saturating_sub(4);
+        engine.draw_box(
             pixmap,
             metrics,
-            " MODULE DETAILS ",
             self.x + 2,
-            self.y,
-            cyan,
-            Some(bg_main),
+            panel_y,
+            panel_w,
+            panel_h,
+            palette.frame,
+        );
+        engine.draw_string_ex(
+            pixmap,
+            metrics,
+            " CONTROLS ",
+            self.x + 4,
+            panel_y,
+            palette.frame,
+            Some(palette.background),
             1,
         );
 
-        let system = self
-            .library
-            .systems
-            .get(self.selected_system)
-            .map_or("UNKNOWN", |s| &s.name);
+        for (i, line) in control_panel(game).iter().enumerate() {
+            let line: String = line.chars().take(panel_w.saturating_sub(4)).collect();
+            engine.draw_string(
+                pixmap,
+                metrics,
+                &line,
+                self.x + 4,
+                panel_y + 1 + i,
+                palette.accent,
+            );
+        }
+    }
 
-        let game = self
-            .library
-            .systems
-            .get(self.selected_system)
-            .and_then(|s| s.games.get(self.selected_game))
-            .unwrap();
+    /// 6. Media box.
+    fn draw_media(
+        &self,
+        pixmap: &mut PixmapMut,
+        engine: &TuiEngine,
+        metrics: &TuiMetrics,
+        game: &Game,
+        (img_y, img_h): (usize, usize),
+    ) {
+        let palette = &engine.theme.palette;
+        let img_w = self.w.saturating_sub(4);
 
-        // 3. Draw Large Title
-        engine.draw_string_ex(
+        // Fill
+        for row in img_y..img_y + img_h {
+            engine.draw_string_ex(
+                pixmap,
+                metrics,
+                &" ".repeat(img_w),
+                self.x + 2,
+                row,
+                Color::TRANSPARENT,
+                Some(palette.image_bg),
+                1,
+            );
+        }
+
+        // Outline (re-uses dynamic width)
+        engine.draw_box(
             pixmap,
             metrics,
-            game.name.as_str(),
             self.x + 2,
-            self.y + 2,
-            white,
-            None,
-            2, // 2x Scale
+            img_y,
+            img_w,
+            img_h,
+            palette.frame,
         );
 
-        // 4. Draw System Info
-        engine.draw_string(
+        let media = game.media.images();
+        let shown_media = media.get(self.media_index % media.len().max(1));
+        if let Some((kind, _)) = shown_media {
+            let label = if media.len() > 1 {
+                format!(
+                    " {} {}/{} ",
+                    kind.label(),
+                    self.media_index % media.len() + 1,
+                    media.len()
+                )
+            } else {
+                format!(" {} ", kind.label())
+            };
+            engine.draw_string_ex(
+                pixmap,
+                metrics,
+                &label,
+                self.x + 4,
+                img_y,
+                palette.frame,
+                Some(palette.image_bg),
+                1,
+            );
+        }
+
+        // 1. Calculate pixel boundaries inside the outline
+        let target_px_w = img_w.saturating_sub(2) as f32 * metrics.char_width;
+        let target_px_h = img_h.saturating_sub(2) as f32 * metrics.char_height;
+        let target_px_x = (self.x + 3) as f32 * metrics.char_width;
+        let target_px_y = (img_y + 1) as f32 * metrics.char_height;
+
+        // 2. Draw the Image (if loaded)
+        let images = self.images.borrow();
+        let image = shown_media.map_or(ImageState::Missing, |(_, p)| images.get(p));
+
+        if let ImageState::Ready(image) = image {
+            let (char_pixmap, _) = self.shown_frame(image, Instant::now());
+            images::draw_fit(
+                pixmap,
+                char_pixmap,
+                target_px_x,
+                target_px_y,
+                target_px_w,
+                target_px_h,
+            );
+        } else {
+            // FALLBACK: Centered "NO SIGNAL" (or a placeholder while decoding)
+            let no_sig = if let ImageState::Loading = image {
+                "ACQUIRING VISUAL FEED…"
+            } else {
+                "NO VISUAL FEED"
+            };
+            let text_x = self.x + 2 + (img_w / 2).saturating_sub(no_sig.chars().count() / 2);
+            engine.draw_string(
+                pixmap,
+                metrics,
+                no_sig,
+                text_x,
+                img_y + (img_h / 2),
+                palette.muted,
+            );
+        }
+    }
+
+    /// 7. Button labels, description, Arcade History and mameinfo, paged through.
+    fn draw_texts(
+        &self,
+        pixmap: &mut PixmapMut,
+        engine: &TuiEngine,
+        metrics: &TuiMetrics,
+        game: &Game,
+        (text_y, text_h): (usize, usize),
+    ) {
+        let palette = &engine.theme.palette;
+        engine.draw_box(
             pixmap,
             metrics,
-            &format!("PLATFORM: {}", system),
             self.x + 2,
-            self.y + 5,
-            green,
+            text_y,
+            self.w.saturating_sub(4),
+            text_h,
+            palette.frame,
         );
-        engine.draw_string(
+
+        let Some((lines, visible)) = self.text_view(game) else {
+            return;
+        };
+        let first = self.text_scroll.min(lines.len().saturating_sub(visible));
+        let shown = &lines[first..lines.len().min(first + visible)];
+        let label = if lines.len() > visible {
+            format!(
+                " ARCHIVE {}-{}/{} ",
+                first + 1,
+                first + shown.len(),
+                lines.len()
+            )
+        } else {
+            " ARCHIVE ".to_string()
+        };
+        engine.draw_string_ex(
             pixmap,
             metrics,
-            &format!(
-                "Filename: {}",
-                game.path
-                    .to_path_buf()
-                    .file_name()
-                    .unwrap()
-                    .to_string_lossy()
-            ),
-            self.x + 2,
-            self.y + 6,
-            green,
+            &label,
+            self.x + 4,
+            text_y,
+            palette.frame,
+            Some(palette.background),
+            1,
         );
+
+        for (i, (line, heading)) in shown.iter().enumerate() {
+            let color = if *heading {
+                palette.accent
+            } else {
+                palette.text
+            };
+            engine.draw_string(pixmap, metrics, line, self.x + 3, text_y + 1 + i, color);
+        }
+    }
+
+    /// 8. Stats footer, on the bottom border.
+    fn draw_footer(
+        &self,
+        pixmap: &mut PixmapMut,
+        engine: &TuiEngine,
+        metrics: &TuiMetrics,
+        game: &Game,
+    ) {
+        let play_count = game.play_count;
+        let rating = game
+            .rating
+            .map_or("N/A".to_string(), |r| format!("{:.1}/5", r * 5.0));
+        let stats: String = format!("PLAY COUNT: {:03} | RATING: {}", play_count, rating)
+            .chars()
+            .take(self.w.saturating_sub(4))
+            .collect();
         engine.draw_string(
             pixmap,
             metrics,
-            &format!("id: {}", game.id),
+            &stats,
             self.x + 2,
-            self.y + 7,
-            green,
+            self.y + self.h.saturating_sub(1),
+            engine.theme.palette.text,
         );
+    }
+}
 
-        // 5. Draw "Image" Placeholder Box
-        let img_w = self.w.saturating_sub(4); // Use dynamic self.w
-        let img_h = 14;
-        let img_y = self.y + 10;
+impl Widget for GameWidget {
+    fn draw(&self, pixmap: &mut PixmapMut, engine: &TuiEngine, metrics: &TuiMetrics) {
+        let palette = &engine.theme.palette;
 
-        // Fill
+        // 1. Draw Container Frame
+        engine.draw_box(
+            pixmap,
+            metrics,
+            self.x,
+            self.y,
+            self.w,
+            self.h,
+            palette.frame,
+        );
         engine.draw_string_ex(
             pixmap,
             metrics,
-            &" ".repeat(img_w),
+            " MODULE DETAILS ",
             self.x + 2,
-            img_y,
-            Color::TRANSPARENT,
-            Some(dark_bg),
+            self.y,
+            palette.frame,
+            Some(palette.background),
             1,
         );
 
-        // Outline (re-uses dynamic width)
-        engine.draw_box(pixmap, metrics, self.x + 2, img_y, img_w, img_h, cyan);
+        let library = self.library.borrow();
+        let system = library
+            .systems
+            .get(self.selected_system)
+            .map_or("UNKNOWN", |s| &s.name);
 
-        let img_w_cells = self.w.saturating_sub(4);
-        let img_h_cells = 14;
-        let img_y = self.y + 10;
+        // An empty library or a filter hiding every game leaves nothing to
+        // show
+        let Some(game) = library
+            .systems
+            .get(self.selected_system)
+            .zip(self.selected_game)
+            .and_then(|(s, g)| s.games.get(g))
+        else {
+            let empty = "NO MODULE SELECTED";
+            engine.draw_string(
+                pixmap,
+                metrics,
+                empty,
+                self.x + (self.w / 2).saturating_sub(empty.chars().count() / 2),
+                self.y + self.h / 2,
+                palette.muted,
+            );
+            return;
+        };
 
-        // 1. Calculate pixel boundaries of our TUI box
-        let target_px_w = img_w_cells as f32 * metrics.char_width;
-        let target_px_h = img_h_cells as f32 * metrics.char_height;
-        let target_px_x = (self.x + 2) as f32 * metrics.char_width;
-        let target_px_y = img_y as f32 * metrics.char_height;
+        // Rows inside the frame; everything below adapts to the panel height
+        let inner_bottom = self.y + self.h.saturating_sub(1);
+        let fits = |row: usize, rows: usize| row + rows <= inner_bottom;
+        // Texts stop short of the right border; only this panel's rect is
+        // cleared when it is repainted on its own
+        let inner_w = self.w.saturating_sub(4);
 
-        // 2. Draw the Image (if loaded)
-        if let Some(ref char_pixmap) = self.current_image {
-            // Calculate scale to fit inside the box while keeping aspect ratio
-            let s_w = target_px_w / char_pixmap.width() as f32;
-            let s_h = target_px_h / char_pixmap.height() as f32;
-            let scale = s_w.min(s_h);
-
-            // Center the image inside the target box
-            let actual_w = char_pixmap.width() as f32 * scale;
-            let actual_h = char_pixmap.height() as f32 * scale;
-            let x_offset = (target_px_w - actual_w) / 2.0;
-            let y_offset = (target_px_h - actual_h) / 2.0;
-
-            let transform = tiny_skia::Transform::from_scale(scale, scale)
-                .post_translate(target_px_x + x_offset, target_px_y + y_offset);
-
-            // Use the 'pixmap' argument passed to the function
-            pixmap.draw_pixmap(
-                0,
-                0,
-                char_pixmap.as_ref(),
-                &tiny_skia::PixmapPaint::default(),
-                transform,
-                None,
-            );
-        } else {
-            // FALLBACK: Centered "NO SIGNAL"
-            let no_sig = "NO VISUAL FEED";
-            let text_x = self.x + 2 + (img_w_cells / 2).saturating_sub(no_sig.len() / 2);
-            engine.draw_string(
+        // 3. Draw Large Title
+        if fits(self.y + 2, 2) {
+            let title: String = game.name.chars().take(inner_w / 2).collect();
+            engine.draw_string_ex(
                 pixmap,
                 metrics,
-                no_sig,
-                text_x,
-                img_y + (img_h_cells / 2),
-                Color::from_rgba8(100, 100, 100, 255),
+                &title,
+                self.x + 2,
+                self.y + 2,
+                palette.text,
+                None,
+                2, // 2x Scale
             );
         }
 
-        // 6. Stats Footer
-        let play_count = (self.selected_game * 3) % 99; // Fake random number
-        let stats = format!("PLAY COUNT: {:03} | RATING: A+", play_count);
-        engine.draw_string(
-            pixmap,
-            metrics,
-            &stats,
-            self.x + 2,
-            self.y + self.h - 1,
-            white,
-        );
+        // 4. Draw System Info
+        let info: [String; INFO_LINES] = [
+            format!("PLATFORM: {}", system),
+            format!(
+                "Filename: {}",
+                game.path
+                    .file_name()
+                    .map_or("".into(), |f| f.to_string_lossy())
+            ),
+            format!("id: {}", game.id),
+            format!(
+                "YEAR: {} | DEVELOPER: {} | PLAYERS: {}",
+                game.release_date
+                    .map_or("UNKNOWN".to_string(), |d| d.year.to_string()),
+                game.developer.as_deref().unwrap_or("UNKNOWN"),
+                game.players_label()
+            ),
+        ];
+        for (i, line) in info.iter().enumerate() {
+            if fits(self.y + 5 + i, 1) {
+                let line: String = line.chars().take(inner_w).collect();
+                engine.draw_string(
+                    pixmap,
+                    metrics,
+                    &line,
+                    self.x + 2,
+                    self.y + 5 + i,
+                    palette.accent,
+                );
+            }
+        }
+
+        // 5. Control panel, then media box and texts share whatever height
+        // is left
+        let panes = self.panes(game);
+        if let Some(controls) = panes.controls {
+            self.draw_controls(pixmap, engine, metrics, game, controls);
+        }
+        let (media, texts) = (panes.media, panes.texts);
+        if media.1 >= 3 {
+            self.draw_media(pixmap, engine, metrics, game, media);
+        }
+        if let Some(texts) = texts {
+            self.draw_texts(pixmap, engine, metrics, game, texts);
+        }
+
+        self.draw_footer(pixmap, engine, metrics, game);
     }
 
     fn set_rect(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        self.dirty |= CellRect::new(x, y, w, h) != self.rect();
         self.x = x;
         self.y = y;
         self.w = w;
         self.h = h;
     }
 
+    fn rect(&self) -> CellRect {
+        CellRect::new(self.x, self.y, self.w, self.h)
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn damage(&mut self, out: &mut Vec<CellRect>) {
+        if std::mem::take(&mut self.dirty) {
+            out.push(self.rect());
+        }
+    }
+
     fn handle_command(&mut self, cmd: ControlCommand) -> UiEvent {
         match cmd {
-            ControlCommand::Action(action_command) => match action_command {
-                ActionCommand::Select => {
-                    UiEvent::LaunchGame(self.selected_system, self.selected_game)
-                }
-                ActionCommand::Back => UiEvent::None,
-            },
+            ControlCommand::Action(ActionCommand::Select) => {
+                self.selected_game.map_or(UiEvent::None, |game| {
+                    UiEvent::LaunchGame(self.selected_system, game)
+                })
+            }
+            ControlCommand::Action(ActionCommand::NextMedia) => {
+                self.media_index += 1;
+                self.load_image();
+                UiEvent::None
+            }
+            ControlCommand::Action(ActionCommand::ScrollTextUp) => {
+                self.scroll_text(false);
+                UiEvent::None
+            }
+            ControlCommand::Action(ActionCommand::ScrollTextDown) => {
+                self.scroll_text(true);
+                UiEvent::None
+            }
             _ => UiEvent::None,
         }
     }
 
-    fn handle_ui_event(&mut self, event: UiEvent) {
+    fn handle_ui_event(&mut self, event: UiEvent) -> UiEvent {
         // Handle UI events if necessary
         match event {
-            UiEvent::SystemChanged(system_idx) => {
+            UiEvent::SystemChanged(system_idx)
+            | UiEvent::JumpToGame(system_idx, _)
+            | UiEvent::LibraryReplaced(system_idx) => {
+                // The list follows up with the GameChanged of its selection
                 self.selected_system = system_idx;
-                self.selected_game = 0;
-                self.load_image();
+                self.selected_game = None;
+                self.text_scroll = 0;
+                self.dirty = true;
             }
             UiEvent::GameChanged(game_idx) => {
                 self.selected_game = game_idx;
+                self.media_index = 0;
+                self.text_scroll = 0;
                 self.load_image();
             }
+            // Play count, favorite or a decoded image to show
+            UiEvent::LaunchGame(..) | UiEvent::UserStateChanged | UiEvent::ImagesLoaded => {
+                self.dirty = true;
+            }
+            // The shown image may have been replaced
+            UiEvent::LibraryChanged => self.load_image(),
             _ => {}
         }
+        UiEvent::None
+    }
+
+    fn next_frame(&mut self, now: Instant) -> Option<Instant> {
+        let path = self.shown_image_path()?;
+        let next = match self.images.borrow().get(&path) {
+            ImageState::Ready(image) => self.shown_frame(image, now).1,
+            _ => None,
+        };
+        // The frame shown changes once its deadline passes
+        self.dirty |= self.frame_due.is_some_and(|due| due <= now);
+        self.frame_due = next;
+        next
+    }
+}
+
+fn has_texts(game: &Game) -> bool {
+    !game.button_labels.is_empty()
+        || game.description.is_some()
+        || game.history.is_some()
+        || game.mame_info.is_some()
+}
+
+/// The texts of the game under their headings, wrapped to `width`, with
+/// the heading lines flagged.
+fn text_lines(game: &Game, width: usize) -> Vec<(String, bool)> {
+    let buttons = (!game.button_labels.is_empty()).then(|| {
+        game.button_labels
+            .iter()
+            .enumerate()
+            .filter(|(_, label)| !label.is_empty())
+            .map(|(i, label)| format!("({}) {}", i + 1, label))
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+    let sections = [
+        ("BUTTONS", &buttons),
+        ("DESCRIPTION", &game.description),
+        ("HISTORY", &game.history),
+        ("MAMEINFO", &game.mame_info),
+    ];
+    let mut lines = Vec::new();
+    for (heading, text) in sections {
+        let Some(text) = text else {
+            continue;
+        };
+        if !lines.is_empty() {
+            lines.push((String::new(), false));
+        }
+        lines.push((heading.to_string(), true));
+        lines.extend(wrap(text, width).into_iter().map(|line| (line, false)));
+    }
+    lines
+}
+
+/// Player 1's sticks and buttons as three rows of glyphs, followed by what
+/// they are:
+///
+/// ```text
+/// ╲│╱  (1) (2) (3)   8-WAY JOYSTICK
+/// ─●─                6 BUTTONS
+/// ╱│╲  (4) (5) (6)
+/// ```
+fn control_panel(game: &Game) -> [String; 3] {
+    let controls: Vec<&Control> = game.controls.iter().filter(|c| c.player <= 1).collect();
+    let mut rows = [String::new(), String::new(), String::new()];
+    for glyph in controls.iter().filter_map(|c| control_glyph(c)) {
+        for (row, glyph) in rows.iter_mut().zip(glyph) {
+            row.push_str(glyph);
+            row.push_str("  ");
+        }
+    }
+
+    // Buttons in two rows, like most panels
+    let buttons = controls.iter().map(|c| c.buttons).max().unwrap_or(0);
+    let top = buttons.div_ceil(2);
+    let button_row = |range: std::ops::RangeInclusive<u8>| {
+        range
+            .map(|n| format!("({})", n))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    rows[0].push_str(&button_row(1..=top));
+    rows[2].push_str(&button_row(top + 1..=buttons));
+
+    let mut kinds: Vec<String> = controls
+        .iter()
+        .filter(|c| c.kind != ControlKind::Buttons)
+        .map(|c| c.label())
+        .collect();
+    kinds.dedup();
+    let summary = [
+        kinds.join(" + "),
+        match buttons {
+            0 => "NO BUTTONS".to_string(),
+            1 => "1 BUTTON".to_string(),
+            n => format!("{} BUTTONS", n),
+        },
+        String::new(),
+    ];
+
+    let width = rows.iter().map(|r| r.chars().count()).max().unwrap_or(0);
+    for (row, summary) in rows.iter_mut().zip(summary).filter(|(_, s)| !s.is_empty()) {
+        let pad = width + 3 - row.chars().count();
+        row.push_str(&" ".repeat(pad));
+        row.push_str(&summary);
+    }
+    rows
+}
+
+fn control_glyph(control: &Control) -> Option<[&'static str; 3]> {
+    Some(match (control.kind, control.ways) {
+        (ControlKind::Joystick, Some(2)) => ["   ", "◄●►", "   "],
+        (ControlKind::Joystick, Some(4)) => [" │ ", "─●─", " │ "],
+        (ControlKind::Joystick, _) => ["╲│╱", "─●─", "╱│╲"],
+        (ControlKind::DoubleJoystick, Some(4)) => [" │   │ ", "─●─ ─●─", " │   │ "],
+        (ControlKind::DoubleJoystick, _) => ["╲│╱ ╲│╱", "─●─ ─●─", "╱│╲ ╱│╲"],
+        (ControlKind::Trackball, _) => ["╭─╮", "│●│", "╰─╯"],
+        (ControlKind::Spinner | ControlKind::Paddle, _) => ["   ", "◄○►", "   "],
+        (ControlKind::Buttons, _) => return None,
+        _ => ["   ", " ◎ ", "   "],
+    })
+}
+
+/// Breaks `text` into lines of at most `width` characters, between words
+/// where possible.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    if width == 0 {
+        return Vec::new();
+    }
+
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut len = 0;
+        for word in paragraph.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            if len > 0 && len + 1 + word.len() > width {
+                lines.push(std::mem::take(&mut line));
+                len = 0;
+            }
+            // Words longer than a line are cut
+            while word.len() > width {
+                if len > 0 {
+                    lines.push(std::mem::take(&mut line));
+                    len = 0;
+                }
+                lines.push(word.drain(..width).collect());
+            }
+            if word.is_empty() {
+                continue;
+            }
+            if len > 0 {
+                line.push(' ');
+                len += 1;
+            }
+            len += word.len();
+            line.extend(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::golden::{dispatch, engine, fixture_images, fixture_library, render};
+    use crate::ui::tui::Rotation;
+
+    #[test]
+    fn texts_stay_inside_the_panel() {
+        // Outside its rect nothing clears what a widget drew there
+        let edge = |name: &str| {
+            let library = fixture_library();
+            library.borrow_mut().systems[0].games[3].name = name.to_string();
+            let mut game = GameWidget::new(library, fixture_images());
+            dispatch(&mut game, UiEvent::GameChanged(Some(3)));
+            let frame = render(&mut game);
+            let rect = game.rect();
+            let metrics = engine().calculate_metrics(frame.width(), frame.height(), Rotation::None);
+            let right = ((rect.x + rect.w) as f32 * metrics.char_width).ceil() as usize;
+            frame
+                .pixels()
+                .chunks_exact(frame.width() as usize)
+                .flat_map(|row| row[right..].iter().map(|px| px.red()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(edge(&"Street Fighter II ".repeat(8)), edge("SF2"));
     }
 }
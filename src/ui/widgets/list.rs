@@ -1,8 +1,10 @@
-use crate::commands::{ControlCommand, NavigationCommand, UiEvent};
-use crate::models::RomLibrary;
+use crate::commands::{ActionCommand, ControlCommand, NavigationCommand, UiEvent};
+use crate::config::Config;
+use crate::models::{Cabinet, Incompatible, SharedLibrary};
 use crate::tui::{TuiEngine, TuiMetrics};
-use std::rc::Rc;
-use tiny_skia::{Color, PixmapMut};
+use crate::ui::images::SharedImages;
+use crate::ui::widgets::common::CellRect;
+use tiny_skia::PixmapMut;
 
 pub struct ListWidget {
     pub title: String,
@@ -10,102 +12,311 @@ pub struct ListWidget {
     pub y: usize,
     pub w: usize,
     pub h: usize,
-    pub library: Rc<RomLibrary>,
+    pub library: SharedLibrary,
+    pub images: SharedImages,
     pub selected_system: usize,
     pub selected_index: usize,
     pub scroll_offset: usize,
+    pub entries: Vec<ListEntry>,
+    pub unfiltered_count: usize,
+    pub search_query: String,
+    pub region_priority: Vec<String>,
+    pub one_game_one_rom: bool,
+    pub cabinet: Option<Cabinet>,
+    dirty: bool,
+}
+
+/// One row of the list: a single game, or in 1G1R mode every copy of a title
+/// with the preferred one first.
+pub struct ListEntry {
+    pub games: Vec<usize>,
+    pub shown: usize,
+}
+
+impl ListEntry {
+    pub fn game(&self) -> usize {
+        self.games[self.shown]
+    }
 }
 
 impl ListWidget {
-    pub fn new(library: Rc<RomLibrary>) -> Self {
-        Self {
+    pub fn new(library: SharedLibrary, images: SharedImages, config: &Config) -> Self {
+        let mut list = Self {
             title: "GAME LIST".to_string(),
             x: 0,
             y: 0,
             w: 0,
             h: 0,
             library,
+            images,
             selected_system: 0,
             selected_index: 0,
             scroll_offset: 0,
+            entries: Vec::new(),
+            unfiltered_count: 0,
+            search_query: String::new(),
+            region_priority: config.region_priority.clone(),
+            one_game_one_rom: config.one_game_one_rom,
+            cabinet: config.cabinet.clone(),
+            dirty: true,
+        };
+        list.rebuild_entries();
+        list
+    }
+
+    fn rebuild_entries(&mut self) {
+        self.dirty = true;
+        let library = self.library.borrow();
+        let Some(system) = library.systems.get(self.selected_system) else {
+            self.entries.clear();
+            self.unfiltered_count = 0;
+            return;
+        };
+
+        let mut groups = if self.one_game_one_rom {
+            system.one_game_one_rom(&self.region_priority)
+        } else {
+            (0..system.games.len()).map(|idx| vec![idx]).collect()
+        };
+        self.unfiltered_count = groups.len();
+
+        // A title stays listed while any of its copies passes the filter
+        let hidden = |idx: usize| {
+            let game = &system.games[idx];
+            !system.filter.matches(game) || self.cabinet.as_ref().is_some_and(|c| c.hides(game))
+        };
+        for group in &mut groups {
+            group.retain(|&idx| !hidden(idx));
+        }
+        groups.retain(|group| !group.is_empty());
+
+        if !self.search_query.trim().is_empty() {
+            groups.retain(|group| {
+                group
+                    .iter()
+                    .any(|&idx| system.games[idx].search_score(&self.search_query).is_some())
+            });
+        }
+
+        // Rows are ordered by their preferred copy
+        groups.sort_by(|a, b| {
+            system
+                .sort_mode
+                .compare(&system.games[a[0]], &system.games[b[0]])
+        });
+
+        self.entries = groups
+            .into_iter()
+            .map(|games| ListEntry { games, shown: 0 })
+            .collect();
+    }
+
+    pub fn selected_game(&self) -> Option<usize> {
+        self.entries.get(self.selected_index).map(ListEntry::game)
+    }
+
+    /// Re-selects `game` after the rows changed, keeping it on screen.
+    fn select_game(&mut self, game: usize) {
+        if let Some(row) = self.entries.iter().position(|e| e.games.contains(&game)) {
+            let entry = &mut self.entries[row];
+            entry.shown = entry.games.iter().position(|&g| g == game).unwrap_or(0);
+            self.selected_index = row;
+        } else {
+            self.selected_index = 0;
+        }
+        self.scroll_offset = 0;
+        self.ensure_visible();
+    }
+
+    /// Announces a new selection (or that no game is listed), queueing its
+    /// image and its neighbours' for decoding so they are ready when the user
+    /// scrolls on.
+    fn game_changed(&self, game: Option<usize>) -> UiEvent {
+        let library = self.library.borrow();
+        if let Some(system) = library.systems.get(self.selected_system) {
+            let sel = self.selected_index;
+            let rows = [
+                Some(sel),
+                sel.checked_add(1),
+                sel.checked_sub(1),
+                sel.checked_add(2),
+                sel.checked_sub(2),
+            ];
+            let paths: Vec<_> = rows
+                .into_iter()
+                .flatten()
+                .filter_map(|row| self.entries.get(row))
+                .filter_map(|entry| system.games[entry.game()].media.primary_image().cloned())
+                .collect();
+            self.images.borrow_mut().focus(&paths);
+        }
+        UiEvent::GameChanged(game)
+    }
+
+    fn ensure_visible(&mut self) {
+        let vis_h = self.h.saturating_sub(2);
+        if self.selected_index < self.scroll_offset {
+            self.scroll_offset = self.selected_index;
+        } else if self.selected_index >= self.scroll_offset + vis_h {
+            self.scroll_offset = self.selected_index + 1 - vis_h.max(1);
+        }
+    }
+
+    fn header(&self) -> String {
+        let mut header = self.title.clone();
+        if self.one_game_one_rom {
+            header.push_str(" (1G1R)");
+        }
+        if let Some(system) = self.library.borrow().systems.get(self.selected_system) {
+            if system.filter.is_active() {
+                header.push_str(&format!(
+                    " (FILTERED {}/{})",
+                    self.entries.len(),
+                    self.unfiltered_count
+                ));
+            }
+            header.push_str(&format!(" | SORT: {}", system.sort_mode.label()));
+        }
+        if !self.search_query.trim().is_empty() {
+            header.push_str(&format!(" | SEARCH: \"{}\"", self.search_query.trim()));
+        }
+        header
+    }
+
+    /// Rebuilds the rows (e.g. after a sort, filter or play count change)
+    /// keeping the current game selected if it is still listed.
+    fn refresh(&mut self) -> UiEvent {
+        let old_game = self.selected_game();
+        self.rebuild_entries();
+        self.select_game(old_game.unwrap_or(0));
+        let game = self.selected_game();
+        if game != old_game {
+            self.game_changed(game)
+        } else {
+            UiEvent::None
         }
     }
 }
 
 impl crate::ui::widgets::common::Widget for ListWidget {
     fn handle_command(&mut self, cmd: ControlCommand) -> UiEvent {
-        let old_idx = self.selected_index;
+        let old_game = self.selected_game();
 
         match cmd {
             ControlCommand::Navigation(nav_command) => match nav_command {
                 NavigationCommand::Up if self.selected_index > 0 => self.selected_index -= 1,
                 NavigationCommand::Down
-                    if self.selected_index
-                        < self.library.systems[self.selected_system]
-                            .games
-                            .len()
-                            .saturating_sub(1) =>
+                    if self.selected_index < self.entries.len().saturating_sub(1) =>
                 {
                     self.selected_index += 1
                 }
                 _ => return UiEvent::None,
             },
+            ControlCommand::Action(ActionCommand::ToggleOneGameOneRom) => {
+                self.one_game_one_rom = !self.one_game_one_rom;
+                self.rebuild_entries();
+                self.select_game(old_game.unwrap_or(0));
+            }
+            ControlCommand::Action(ActionCommand::NextAlternate) => {
+                if let Some(entry) = self.entries.get_mut(self.selected_index) {
+                    entry.shown = (entry.shown + 1) % entry.games.len();
+                }
+            }
+            ControlCommand::Action(ActionCommand::CycleSort) => {
+                if let Some(system) = self
+                    .library
+                    .borrow_mut()
+                    .systems
+                    .get_mut(self.selected_system)
+                {
+                    system.sort_mode = system.sort_mode.next();
+                }
+                return UiEvent::UserStateChanged;
+            }
+            ControlCommand::Action(ActionCommand::ToggleFavorite) => {
+                let Some(game_idx) = old_game else {
+                    return UiEvent::None;
+                };
+                if let Some(game) = self
+                    .library
+                    .borrow_mut()
+                    .systems
+                    .get_mut(self.selected_system)
+                    .and_then(|s| s.games.get_mut(game_idx))
+                {
+                    game.favorite = !game.favorite;
+                }
+                return UiEvent::UserStateChanged;
+            }
             _ => return UiEvent::None,
         }
 
-        if old_idx != self.selected_index {
-            let vis_h = self.h.saturating_sub(2);
-            if self.selected_index < self.scroll_offset {
-                self.scroll_offset = self.selected_index;
-            } else if self.selected_index >= self.scroll_offset + vis_h {
-                self.scroll_offset = self.selected_index - vis_h + 1;
-            }
-            return UiEvent::GameChanged(self.selected_index);
+        self.dirty = true;
+        self.ensure_visible();
+        let game = self.selected_game();
+        if game != old_game {
+            self.game_changed(game)
+        } else {
+            UiEvent::None
         }
-        UiEvent::None
     }
 
     fn draw(&self, pixmap: &mut PixmapMut, engine: &TuiEngine, metrics: &TuiMetrics) {
-        // COLORS (BGR SWAP applied for direct buffer writing)
-        // If Cyan looks Yellow, we must write (255, 255, 0) to get (0, 255, 255)
-        let cyan = Color::from_rgba8(255, 255, 0, 255);
-        let green = Color::from_rgba8(0, 255, 0, 255);
-        let white = Color::from_rgba8(255, 255, 255, 255);
-        let grey = Color::from_rgba8(180, 180, 180, 255);
-
-        // Dark Cyan Highlight (Swapped R/B)
-        let highlight_bg = Color::from_rgba8(60, 60, 0, 255);
-
-        // Main Background (Dark Green/Black)
-        let bg_main = Color::from_rgba8(5, 10, 0, 255);
+        let palette = &engine.theme.palette;
 
         // 1. Draw Outer Frame
-        engine.draw_box(pixmap, metrics, self.x, self.y, self.w, self.h, cyan);
+        engine.draw_box(
+            pixmap,
+            metrics,
+            self.x,
+            self.y,
+            self.w,
+            self.h,
+            palette.frame,
+        );
 
         // 2. Draw Title
         engine.draw_string_ex(
             pixmap,
             metrics,
-            &format!(" {} ", self.title),
+            &format!(" {} ", self.header()),
             self.x + 2,
             self.y,
-            green,
-            Some(bg_main),
+            palette.accent,
+            Some(palette.background),
             1,
         );
 
         // 3. Draw Items
+        let library = self.library.borrow();
         let vis_h = self.h.saturating_sub(2);
         for i in 0..vis_h {
             let idx = i + self.scroll_offset;
-            if idx >= self.library.systems[self.selected_system].games.len() {
+            let Some(entry) = self.entries.get(idx) else {
                 break;
-            }
+            };
 
             let text_w = self.w.saturating_sub(4);
-            let raw_text = &self.library.systems[self.selected_system].games[idx].name;
-            let display_text = if raw_text.len() > text_w {
-                format!("{}…", &raw_text[..text_w.saturating_sub(1)])
+            let game = &library.systems[self.selected_system].games[entry.game()];
+            let playable = self
+                .cabinet
+                .as_ref()
+                .is_none_or(|c| c.incompatible != Incompatible::Dim || c.can_play(game));
+            let marker = if game.favorite { "◆ " } else { "" };
+            let raw_text = if entry.games.len() > 1 {
+                format!(
+                    "{}{} [{}/{}]",
+                    marker,
+                    game.name,
+                    entry.shown + 1,
+                    entry.games.len()
+                )
+            } else {
+                format!("{}{}", marker, game.name)
+            };
+            let display_text = if raw_text.chars().count() > text_w {
+                let cut: String = raw_text.chars().take(text_w.saturating_sub(1)).collect();
+                format!("{}…", cut)
             } else {
                 format!("{:<width$}", raw_text, width = text_w)
             };
@@ -118,8 +329,12 @@ impl crate::ui::widgets::common::Widget for ListWidget {
                     &display_text,
                     self.x + 2,
                     self.y + 1 + i,
-                    white,
-                    Some(highlight_bg),
+                    if playable {
+                        palette.text
+                    } else {
+                        palette.muted
+                    },
+                    Some(palette.highlight_bg),
                     1,
                 );
             } else {
@@ -130,15 +345,19 @@ impl crate::ui::widgets::common::Widget for ListWidget {
                     &display_text,
                     self.x + 2,
                     self.y + 1 + i,
-                    grey,
+                    if playable {
+                        palette.text_dim
+                    } else {
+                        palette.muted
+                    },
                 );
             }
         }
 
         // 4. Draw Scrollbar
-        if self.library.systems[self.selected_system].games.len() > vis_h {
+        if self.entries.len() > vis_h {
             let bar_x = self.x + self.w - 1;
-            let total_items = self.library.systems[self.selected_system].games.len() as f32;
+            let total_items = self.entries.len() as f32;
             let track_h = vis_h as f32;
 
             // Calculate handle relative position
@@ -155,8 +374,8 @@ impl crate::ui::widgets::common::Widget for ListWidget {
                     symbol,
                     bar_x,
                     self.y + 1 + i,
-                    cyan,
-                    Some(bg_main),
+                    palette.frame,
+                    Some(palette.background),
                     1,
                 );
             }
@@ -164,20 +383,53 @@ impl crate::ui::widgets::common::Widget for ListWidget {
     }
 
     fn set_rect(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        self.dirty |= CellRect::new(x, y, w, h) != self.rect();
         self.x = x;
         self.y = y;
         self.w = w;
         self.h = h;
     }
 
-    fn handle_ui_event(&mut self, event: UiEvent) {
-        match event {
-            UiEvent::SystemChanged(system_idx) => {
-                self.selected_system = system_idx;
-                self.selected_index = 0;
-                self.scroll_offset = 0;
-            }
-            _ => {}
+    fn rect(&self) -> CellRect {
+        CellRect::new(self.x, self.y, self.w, self.h)
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn damage(&mut self, out: &mut Vec<CellRect>) {
+        if std::mem::take(&mut self.dirty) {
+            out.push(self.rect());
         }
     }
+
+    fn handle_ui_event(&mut self, event: UiEvent) -> UiEvent {
+        if let UiEvent::SystemChanged(system_idx) | UiEvent::LibraryReplaced(system_idx) = event {
+            self.selected_system = system_idx;
+            self.selected_index = 0;
+            self.scroll_offset = 0;
+            self.rebuild_entries();
+            // The first row is not necessarily the system's first game
+            return self.game_changed(self.selected_game());
+        } else if let UiEvent::LaunchGame(system_idx, _) = event
+            && system_idx == self.selected_system
+        {
+            // Play count and last played order may have changed
+            return self.refresh();
+        } else if event == UiEvent::UserStateChanged || event == UiEvent::LibraryChanged {
+            // Sort mode, filter, favorites or the sorted fields changed
+            return self.refresh();
+        } else if let UiEvent::SearchChanged(query) = event {
+            self.search_query = query;
+            return self.refresh();
+        } else if let UiEvent::JumpToGame(system_idx, game_idx) = event {
+            self.selected_system = system_idx;
+            self.search_query.clear();
+            self.rebuild_entries();
+            self.select_game(game_idx);
+            return self.game_changed(self.selected_game());
+        }
+        UiEvent::None
+    }
 }
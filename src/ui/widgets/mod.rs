@@ -1,9 +1,16 @@
+pub mod attract;
 pub mod carousel;
 pub(crate) mod common;
+pub mod filter;
 pub mod game;
 pub mod list;
+pub mod overlay;
 pub mod panel;
+pub mod search;
 
+pub use attract::AttractWidget;
 pub use carousel::CarouselWidget;
+pub use filter::FilterPanelWidget;
 pub use game::GameWidget;
 pub use list::ListWidget;
+pub use search::SearchWidget;
@@ -0,0 +1,95 @@
+use crate::{
+    commands::{ControlCommand, UiEvent},
+    ui::tui::{TuiEngine, TuiMetrics},
+    ui::widgets::common::{CellRect, Modal, Widget},
+};
+use std::time::Instant;
+use tiny_skia::PixmapMut;
+
+/// Stacks a modal widget on top of a base widget. Both share the same rect;
+/// the modal sees every command first (so it can open itself) and swallows
+/// them while active.
+pub struct OverlayWidget<B: Widget, M: Widget + Modal> {
+    base: B,
+    modal: M,
+}
+
+impl<B: Widget, M: Widget + Modal> OverlayWidget<B, M> {
+    pub fn new(base: B, modal: M) -> Self {
+        Self { base, modal }
+    }
+
+    pub fn modal(&self) -> &M {
+        &self.modal
+    }
+}
+
+impl<B: Widget, M: Widget + Modal> Widget for OverlayWidget<B, M> {
+    fn draw(&self, pixmap: &mut PixmapMut, engine: &TuiEngine, metrics: &TuiMetrics) {
+        self.base.draw(pixmap, engine, metrics);
+        if self.modal.is_active() {
+            self.modal.draw(pixmap, engine, metrics);
+        }
+    }
+
+    fn set_rect(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        self.base.set_rect(x, y, w, h);
+        self.modal.set_rect(x, y, w, h);
+    }
+
+    fn rect(&self) -> CellRect {
+        self.base.rect()
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.base.is_dirty() || self.modal.is_dirty()
+    }
+
+    fn damage(&mut self, out: &mut Vec<CellRect>) {
+        let mut damage = Vec::new();
+        self.base.damage(&mut damage);
+
+        // Opening or closing the modal may uncover any of the base, and an
+        // open one is painted over whatever changed beneath it
+        if self.modal.is_dirty() || (self.modal.is_active() && !damage.is_empty()) {
+            damage.clear();
+            self.modal.damage(&mut damage);
+            out.push(self.rect());
+        } else {
+            out.append(&mut damage);
+        }
+    }
+
+    fn draw_damaged(
+        &self,
+        pixmap: &mut PixmapMut,
+        engine: &TuiEngine,
+        metrics: &TuiMetrics,
+        damage: &[CellRect],
+    ) {
+        self.base.draw_damaged(pixmap, engine, metrics, damage);
+        if self.modal.is_active() {
+            self.modal.draw_damaged(pixmap, engine, metrics, damage);
+        }
+    }
+
+    fn handle_command(&mut self, cmd: ControlCommand) -> UiEvent {
+        let was_active = self.modal.is_active();
+        let event = self.modal.handle_command(cmd.clone());
+        if was_active || self.modal.is_active() || event != UiEvent::None {
+            return event;
+        }
+        self.base.handle_command(cmd)
+    }
+
+    fn handle_ui_event(&mut self, event: UiEvent) -> UiEvent {
+        let e1 = self.base.handle_ui_event(event.clone());
+        let e2 = self.modal.handle_ui_event(event);
+        e1.and(e2)
+    }
+
+    fn next_frame(&mut self, now: Instant) -> Option<Instant> {
+        let (a, b) = (self.base.next_frame(now), self.modal.next_frame(now));
+        a.into_iter().chain(b).min()
+    }
+}
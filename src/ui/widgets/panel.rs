@@ -1,7 +1,10 @@
 use crate::{
     commands::{ControlCommand, UiEvent},
-    ui::widgets::common::{Container, Widget},
+    ui::tui::{TuiEngine, TuiMetrics},
+    ui::widgets::common::{CellRect, Container, Widget},
 };
+use std::time::Instant;
+use tiny_skia::PixmapMut;
 
 pub struct SplitPanelWidget<L: Widget, R: Widget> {
     left: L,
@@ -78,12 +81,7 @@ impl<L: Widget, R: Widget> Container for SplitPanelWidget<L, R> {
 }
 
 impl<L: Widget, R: Widget> Widget for SplitPanelWidget<L, R> {
-    fn draw(
-        &self,
-        pixmap: &mut tiny_skia::PixmapMut,
-        engine: &crate::ui::tui::TuiEngine,
-        metrics: &crate::ui::tui::TuiMetrics,
-    ) {
+    fn draw(&self, pixmap: &mut PixmapMut, engine: &TuiEngine, metrics: &TuiMetrics) {
         self.left.draw(pixmap, engine, metrics);
         self.right.draw(pixmap, engine, metrics);
     }
@@ -96,6 +94,30 @@ impl<L: Widget, R: Widget> Widget for SplitPanelWidget<L, R> {
         self.arrange_widgets();
     }
 
+    fn rect(&self) -> CellRect {
+        CellRect::new(self.x, self.y, self.w, self.h)
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.left.is_dirty() || self.right.is_dirty()
+    }
+
+    fn damage(&mut self, out: &mut Vec<CellRect>) {
+        self.left.damage(out);
+        self.right.damage(out);
+    }
+
+    fn draw_damaged(
+        &self,
+        pixmap: &mut PixmapMut,
+        engine: &TuiEngine,
+        metrics: &TuiMetrics,
+        damage: &[CellRect],
+    ) {
+        self.left.draw_damaged(pixmap, engine, metrics, damage);
+        self.right.draw_damaged(pixmap, engine, metrics, damage);
+    }
+
     fn handle_command(&mut self, cmd: ControlCommand) -> UiEvent {
         let e1 = self.left.handle_command(cmd.clone());
         if e1 != UiEvent::None {
@@ -104,8 +126,14 @@ impl<L: Widget, R: Widget> Widget for SplitPanelWidget<L, R> {
         self.right.handle_command(cmd)
     }
 
-    fn handle_ui_event(&mut self, event: UiEvent) {
-        self.left.handle_ui_event(event.clone());
-        self.right.handle_ui_event(event);
+    fn handle_ui_event(&mut self, event: UiEvent) -> UiEvent {
+        let e1 = self.left.handle_ui_event(event.clone());
+        let e2 = self.right.handle_ui_event(event);
+        e1.and(e2)
+    }
+
+    fn next_frame(&mut self, now: Instant) -> Option<Instant> {
+        let (a, b) = (self.left.next_frame(now), self.right.next_frame(now));
+        a.into_iter().chain(b).min()
     }
 }
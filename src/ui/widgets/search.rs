@@ -0,0 +1,395 @@
+use crate::commands::{ActionCommand, ControlCommand, NavigationCommand, UiEvent};
+use crate::models::{Cabinet, SharedLibrary};
+use crate::tui::{TuiEngine, TuiMetrics};
+use crate::ui::widgets::common::{CellRect, Modal, Widget};
+use tiny_skia::{Color, PixmapMut};
+
+const KEYS: [&[&str]; 5] = [
+    &["A", "B", "C", "D", "E", "F", "G", "H", "I", "J"],
+    &["K", "L", "M", "N", "O", "P", "Q", "R", "S", "T"],
+    &["U", "V", "W", "X", "Y", "Z", "0", "1", "2", "3"],
+    &["4", "5", "6", "7", "8", "9", "-", ".", "'", "&"],
+    &["SPACE", "DEL", "CLEAR", "SCOPE", "DONE"],
+];
+const MAX_RESULTS: usize = 10;
+const PANEL_W: usize = 52;
+
+/// Modal search with an on-screen keyboard for cabinets without one. Every
+/// key press narrows the game list live; picking one of the results jumps
+/// the carousel and list to that game.
+pub struct SearchWidget {
+    library: SharedLibrary,
+    cabinet: Option<Cabinet>,
+    selected_system: usize,
+    active: bool,
+    query: String,
+    whole_library: bool,
+    results: Vec<(usize, usize)>,
+    cursor_row: usize, // keyboard rows first, then result rows
+    cursor_col: usize,
+    dirty: bool,
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+}
+
+impl SearchWidget {
+    pub fn new(library: SharedLibrary, cabinet: Option<Cabinet>) -> Self {
+        Self {
+            library,
+            cabinet,
+            selected_system: 0,
+            active: false,
+            query: String::new(),
+            whole_library: false,
+            results: Vec::new(),
+            cursor_row: 0,
+            cursor_col: 0,
+            dirty: true,
+            x: 0,
+            y: 0,
+            w: 0,
+            h: 0,
+        }
+    }
+
+    fn update_results(&mut self) {
+        self.results.clear();
+        if self.query.trim().is_empty() {
+            return;
+        }
+
+        let library = self.library.borrow();
+        let mut hits: Vec<(u32, usize, usize)> = Vec::new();
+        for (system_idx, system) in library.systems.iter().enumerate() {
+            if !self.whole_library && system_idx != self.selected_system {
+                continue;
+            }
+            for (game_idx, game) in system.games.iter().enumerate() {
+                // Only games the list shows can be jumped to
+                if !system.filter.matches(game)
+                    || self.cabinet.as_ref().is_some_and(|c| c.hides(game))
+                {
+                    continue;
+                }
+                if let Some(score) = game.search_score(&self.query) {
+                    hits.push((score, system_idx, game_idx));
+                }
+            }
+        }
+
+        // Best score first, shorter names break ties
+        hits.sort_by_key(|&(score, s, g)| {
+            (
+                std::cmp::Reverse(score),
+                library.systems[s].games[g].name.len(),
+            )
+        });
+        self.results = hits
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, s, g)| (s, g))
+            .collect();
+    }
+
+    fn press_key(&mut self) -> UiEvent {
+        let key = KEYS[self.cursor_row][self.cursor_col];
+        match key {
+            "SPACE" => self.query.push(' '),
+            "DEL" => {
+                self.query.pop();
+            }
+            "CLEAR" => self.query.clear(),
+            "SCOPE" => {
+                self.whole_library = !self.whole_library;
+                self.update_results();
+                return UiEvent::None;
+            }
+            "DONE" => {
+                // Keep the list narrowed to the query
+                self.active = false;
+                return UiEvent::None;
+            }
+            _ => self.query.push_str(key),
+        }
+        self.update_results();
+        UiEvent::SearchChanged(self.query.clone())
+    }
+
+    /// The panel box as `(x, y, w, h)` and the spacing of the keyboard rows.
+    fn geometry(&self) -> (usize, usize, usize, usize, usize) {
+        // Docked right so the narrowed game list stays visible
+        let box_w = PANEL_W.min(self.w);
+        // Keyboard rows are spaced out for legibility when there is room
+        let key_step = if self.h >= KEYS.len() * 2 + MAX_RESULTS + 6 {
+            2
+        } else {
+            1
+        };
+        let box_h = (KEYS.len() * key_step + MAX_RESULTS + 6).min(self.h);
+        let box_x = self.x + self.w.saturating_sub(box_w + 1);
+        let box_y = self.y + self.h.saturating_sub(box_h) / 2;
+        (box_x, box_y, box_w, box_h, key_step)
+    }
+
+    /// How many results fit between the keyboard and the bottom border.
+    fn visible_results(&self) -> usize {
+        let (_, _, _, box_h, key_step) = self.geometry();
+        let rows = (box_h.saturating_sub(1)).saturating_sub(4 + KEYS.len() * key_step);
+        self.results.len().min(rows)
+    }
+
+    /// Moves the cursor up onto the last result shown, if it is below it.
+    fn clamp_cursor(&mut self) {
+        let rows = KEYS.len() + self.visible_results();
+        self.cursor_row = self.cursor_row.min(rows - 1);
+    }
+
+    fn move_cursor(&mut self, nav: NavigationCommand) {
+        // Only the results on screen can be picked
+        let rows = KEYS.len() + self.visible_results();
+        match nav {
+            NavigationCommand::Up => self.cursor_row = self.cursor_row.saturating_sub(1),
+            NavigationCommand::Down => self.cursor_row = (self.cursor_row + 1).min(rows - 1),
+            NavigationCommand::Left => self.cursor_col = self.cursor_col.saturating_sub(1),
+            NavigationCommand::Right => self.cursor_col += 1,
+            NavigationCommand::None => {}
+        }
+        if let Some(row) = KEYS.get(self.cursor_row) {
+            self.cursor_col = self.cursor_col.min(row.len() - 1);
+        }
+    }
+}
+
+impl Modal for SearchWidget {
+    fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+impl Widget for SearchWidget {
+    fn draw(&self, pixmap: &mut PixmapMut, engine: &TuiEngine, metrics: &TuiMetrics) {
+        let palette = &engine.theme.palette;
+        let (box_x, box_y, box_w, box_h, key_step) = self.geometry();
+        let inner_w = box_w.saturating_sub(4);
+
+        for row in 0..box_h {
+            engine.draw_string_ex(
+                pixmap,
+                metrics,
+                &" ".repeat(box_w),
+                box_x,
+                box_y + row,
+                Color::TRANSPARENT,
+                Some(palette.panel_bg),
+                1,
+            );
+        }
+        engine.draw_box(pixmap, metrics, box_x, box_y, box_w, box_h, palette.frame);
+        let scope = if self.whole_library {
+            "ALL SYSTEMS"
+        } else {
+            "THIS SYSTEM"
+        };
+        engine.draw_string_ex(
+            pixmap,
+            metrics,
+            &format!(" SEARCH: {} ", scope),
+            box_x + 2,
+            box_y,
+            palette.accent,
+            Some(palette.panel_bg),
+            1,
+        );
+
+        // Query line with a block cursor
+        let query: String = format!("> {}█", self.query).chars().take(inner_w).collect();
+        engine.draw_string(pixmap, metrics, &query, box_x + 2, box_y + 2, palette.text);
+
+        // Keyboard: each key is a fixed-width cell
+        let keys_y = box_y + 4;
+        for (r, row) in KEYS.iter().enumerate() {
+            let cell_w = inner_w / row.len();
+            for (c, key) in row.iter().enumerate() {
+                let label = format!("{:^width$}", key, width = cell_w);
+                let (col, line) = (box_x + 2 + c * cell_w, keys_y + r * key_step);
+                if r == self.cursor_row && c == self.cursor_col {
+                    engine.draw_string_ex(
+                        pixmap,
+                        metrics,
+                        &label,
+                        col,
+                        line,
+                        palette.text,
+                        Some(palette.highlight_bg),
+                        1,
+                    );
+                } else {
+                    engine.draw_string(pixmap, metrics, &label, col, line, palette.text_dim);
+                }
+            }
+        }
+
+        // Results
+        let library = self.library.borrow();
+        let results_y = keys_y + KEYS.len() * key_step;
+        for (i, &(s, g)) in self.results.iter().take(self.visible_results()).enumerate() {
+            let line = results_y + i;
+            let system = &library.systems[s];
+            let text = format!("{} › {}", system.name, system.games[g].name);
+            let text: String = format!("{:<width$}", text, width = inner_w)
+                .chars()
+                .take(inner_w)
+                .collect();
+
+            if KEYS.len() + i == self.cursor_row {
+                engine.draw_string_ex(
+                    pixmap,
+                    metrics,
+                    &text,
+                    box_x + 2,
+                    line,
+                    palette.text,
+                    Some(palette.highlight_bg),
+                    1,
+                );
+            } else {
+                engine.draw_string(pixmap, metrics, &text, box_x + 2, line, palette.frame);
+            }
+        }
+    }
+
+    fn set_rect(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        self.dirty |= CellRect::new(x, y, w, h) != self.rect();
+        self.x = x;
+        self.y = y;
+        self.w = w;
+        self.h = h;
+        // A smaller panel may no longer show the result under the cursor
+        self.clamp_cursor();
+    }
+
+    fn rect(&self) -> CellRect {
+        CellRect::new(self.x, self.y, self.w, self.h)
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn damage(&mut self, out: &mut Vec<CellRect>) {
+        if std::mem::take(&mut self.dirty) {
+            out.push(self.rect());
+        }
+    }
+
+    fn handle_command(&mut self, cmd: ControlCommand) -> UiEvent {
+        // Opening, closing or any input while open changes the panel
+        self.dirty |= self.active;
+        if !self.active {
+            if cmd == ControlCommand::Action(ActionCommand::OpenSearch) {
+                self.active = true;
+                self.dirty = true;
+                self.cursor_row = 0;
+                self.cursor_col = 0;
+                self.update_results();
+            }
+            return UiEvent::None;
+        }
+
+        match cmd {
+            ControlCommand::Navigation(nav) => {
+                self.move_cursor(nav);
+                UiEvent::None
+            }
+            ControlCommand::Action(ActionCommand::Select) if self.cursor_row < KEYS.len() => {
+                self.press_key()
+            }
+            ControlCommand::Action(ActionCommand::Select) => {
+                let Some(&(system, game)) = self.results.get(self.cursor_row - KEYS.len()) else {
+                    return UiEvent::None;
+                };
+                self.active = false;
+                self.query.clear();
+                self.results.clear();
+                UiEvent::JumpToGame(system, game)
+            }
+            ControlCommand::Action(ActionCommand::Back) => {
+                // Abandon the search and restore the full list
+                self.active = false;
+                self.query.clear();
+                self.results.clear();
+                UiEvent::SearchChanged(String::new())
+            }
+            ControlCommand::Action(ActionCommand::OpenSearch) => {
+                self.active = false;
+                UiEvent::None
+            }
+            _ => UiEvent::None,
+        }
+    }
+
+    fn handle_ui_event(&mut self, event: UiEvent) -> UiEvent {
+        match event {
+            UiEvent::SystemChanged(system_idx) | UiEvent::JumpToGame(system_idx, _) => {
+                self.selected_system = system_idx;
+            }
+            UiEvent::LibraryReplaced(system_idx) => {
+                // The results point into the old library
+                self.selected_system = system_idx;
+                self.update_results();
+                self.clamp_cursor();
+                self.dirty |= self.active;
+            }
+            _ => {}
+        }
+        UiEvent::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::golden::fixture_library;
+
+    const SELECT: ControlCommand = ControlCommand::Action(ActionCommand::Select);
+    const DOWN: ControlCommand = ControlCommand::Navigation(NavigationCommand::Down);
+
+    #[test]
+    fn results_skip_filtered_games() {
+        let library = fixture_library();
+        library.borrow_mut().systems[0].filter.decade = Some(1980);
+        let mut search = SearchWidget::new(library.clone(), None);
+        search.set_rect(0, 0, 60, 30);
+        search.handle_command(ControlCommand::Action(ActionCommand::OpenSearch));
+        search.handle_command(SELECT); // "A"
+
+        let library = library.borrow();
+        let system = &library.systems[0];
+        assert!(!search.results.is_empty());
+        assert!(
+            search
+                .results
+                .iter()
+                .all(|&(s, g)| s == 0 && system.filter.matches(&system.games[g]))
+        );
+    }
+
+    #[test]
+    fn search_cursor_stays_on_visible_results() {
+        // Typing "A" finds two games, but the short panel shows only one
+        let pick = |downs: usize| {
+            let mut search = SearchWidget::new(fixture_library(), None);
+            search.set_rect(0, 0, 60, 11);
+            search.handle_command(ControlCommand::Action(ActionCommand::OpenSearch));
+            search.handle_command(SELECT);
+            for _ in 0..downs {
+                search.handle_command(DOWN);
+            }
+            search.handle_command(SELECT)
+        };
+        assert!(matches!(pick(5), UiEvent::JumpToGame(0, _)));
+        assert_eq!(pick(6), pick(5));
+    }
+}
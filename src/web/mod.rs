@@ -0,0 +1,494 @@
+use crate::metadata::GameMetadata;
+use crate::models::{MediaKind, MediaPaths};
+use serde_json::{Value, json};
+use std::fmt::Display;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+use std::time::Duration;
+
+const PAGE: &str = include_str!("index.html");
+/// Largest request body taken, i.e. the largest image that can be uploaded.
+const MAX_BODY: usize = 16 * 1024 * 1024;
+/// How long a connection may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A request for the app to answer. Games are addressed by system name and
+/// game id, which (unlike indices) survive sorting and rescans.
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    /// `GET /api/systems`
+    Systems,
+    /// `GET /api/systems/{system}/games`
+    Games { system: String },
+    /// `PUT /api/systems/{system}/games/{game}` with the fields to change.
+    /// Media can only be set by uploading them.
+    EditGame {
+        system: String,
+        game: String,
+        edit: Box<GameMetadata>,
+    },
+    /// `POST /api/systems/{system}/games/{game}/favorite`
+    ToggleFavorite { system: String, game: String },
+    /// `GET /api/systems/{system}/games/{game}/media/{kind}`
+    Media {
+        system: String,
+        game: String,
+        kind: MediaKind,
+    },
+    /// `PUT /api/systems/{system}/games/{game}/media/{kind}` with the image
+    /// file as the body.
+    UploadMedia {
+        system: String,
+        game: String,
+        kind: MediaKind,
+        data: Vec<u8>,
+    },
+    /// `POST /api/rescan`
+    Rescan,
+}
+
+#[derive(Debug)]
+pub struct Response {
+    status: u16,
+    content_type: String,
+    body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(status: u16, body: Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json".into(),
+            body: body.to_string().into_bytes(),
+        }
+    }
+
+    /// `{"error": message}`
+    pub fn error(status: u16, message: impl Display) -> Self {
+        Self::json(status, json!({ "error": message.to_string() }))
+    }
+
+    pub fn file(content_type: &str, body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            content_type: content_type.into(),
+            body,
+        }
+    }
+}
+
+/// Where to send the response to a request.
+pub struct Responder {
+    tx: Sender<Response>,
+}
+
+impl Responder {
+    pub fn respond(self, response: Response) {
+        // The client may have given up waiting
+        let _ = self.tx.send(response);
+    }
+}
+
+/// Minimal HTTP/1.1 server for the web management page. Like the control
+/// socket, connections are served on background threads and the app answers
+/// their requests in between frames; the page itself and malformed requests
+/// are answered without the app.
+pub struct WebServer {
+    address: SocketAddr,
+    requests: Receiver<(Request, Responder)>,
+}
+
+impl WebServer {
+    /// Listens on `address`; `wake` is called whenever a request comes in.
+    pub fn bind<W>(address: &str, wake: W) -> io::Result<Self>
+    where
+        W: Fn() + Clone + Send + 'static,
+    {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let (tx, requests) = channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (tx, wake) = (tx.clone(), wake.clone());
+                thread::spawn(move || serve(stream, tx, wake));
+            }
+        });
+        Ok(Self { address, requests })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// The next request for the app to answer.
+    pub fn poll(&self) -> Option<(Request, Responder)> {
+        self.requests.try_recv().ok()
+    }
+}
+
+/// Answers one request per connection.
+fn serve<W: Fn()>(stream: TcpStream, requests: Sender<(Request, Responder)>, wake: W) {
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let response = match read_request(&stream)
+        .and_then(|(method, target, body)| route(&method, &target, body))
+    {
+        Ok(request) => {
+            let (tx, rx) = channel();
+            if requests.send((request, Responder { tx })).is_ok() {
+                wake();
+            }
+            rx.recv()
+                .unwrap_or_else(|_| Response::error(503, "osiris is shutting down"))
+        }
+        Err(response) => response,
+    };
+    let _ = write_response(stream, response);
+}
+
+/// Method, target and body of the request on `stream`. Requests a browser
+/// sends on behalf of another site's page are refused.
+fn read_request(stream: &TcpStream) -> Result<(String, String, Vec<u8>), Response> {
+    let bad_request = |e: io::Error| Response::error(400, e);
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(bad_request)?;
+    let mut fields = line.split_whitespace();
+    let (Some(method), Some(target)) = (fields.next(), fields.next()) else {
+        return Err(Response::error(400, "malformed request line"));
+    };
+    let (method, target) = (method.to_string(), target.to_string());
+
+    let mut length = 0;
+    let (mut host, mut origin) = (None, None);
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(bad_request)? == 0 {
+            return Err(Response::error(400, "unexpected end of headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let (name, value) = (name.trim(), value.trim());
+        if name.eq_ignore_ascii_case("content-length") {
+            length = value
+                .parse()
+                .map_err(|_| Response::error(400, "invalid Content-Length"))?;
+        } else if name.eq_ignore_ascii_case("host") {
+            host = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("origin") {
+            origin = Some(value.to_string());
+        }
+    }
+    if !same_origin(origin.as_deref(), host.as_deref()) {
+        return Err(Response::error(
+            403,
+            "cross-origin requests are not allowed",
+        ));
+    }
+
+    if length > MAX_BODY {
+        return Err(Response::error(413, "request body too large"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(bad_request)?;
+    Ok((method, target, body))
+}
+
+/// What the app is asked for. The page itself and requests that cannot be
+/// served come back as `Err`, to be answered right away.
+fn route(method: &str, target: &str, body: Vec<u8>) -> Result<Request, Response> {
+    let path = target.split(['?', '#']).next().unwrap_or("");
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(percent_decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let owned = |s: &str| s.to_string();
+
+    match (method, segments.as_slice()) {
+        ("GET", [] | ["index.html"]) => Err(Response::file(
+            "text/html; charset=utf-8",
+            PAGE.as_bytes().to_vec(),
+        )),
+        ("GET", ["api", "systems"]) => Ok(Request::Systems),
+        ("GET", ["api", "systems", s, "games"]) => Ok(Request::Games { system: owned(s) }),
+        ("PUT", ["api", "systems", s, "games", g]) => {
+            let edit: Box<GameMetadata> =
+                serde_json::from_slice(&body).map_err(|e| Response::error(400, e))?;
+            if edit.media != MediaPaths::default() {
+                return Err(Response::error(
+                    400,
+                    "media can only be changed by uploading them",
+                ));
+            }
+            Ok(Request::EditGame {
+                system: owned(s),
+                game: owned(g),
+                edit,
+            })
+        }
+        ("POST", ["api", "systems", s, "games", g, "favorite"]) => Ok(Request::ToggleFavorite {
+            system: owned(s),
+            game: owned(g),
+        }),
+        ("GET" | "PUT", ["api", "systems", s, "games", g, "media", kind]) => {
+            let kind = image_kind(kind)
+                .ok_or_else(|| Response::error(404, format!("no image kind {:?}", kind)))?;
+            let (system, game) = (owned(s), owned(g));
+            Ok(if method == "GET" {
+                Request::Media { system, game, kind }
+            } else {
+                Request::UploadMedia {
+                    system,
+                    game,
+                    kind,
+                    data: body,
+                }
+            })
+        }
+        ("POST", ["api", "rescan"]) => Ok(Request::Rescan),
+        _ => Err(Response::error(
+            404,
+            format!("no route for {} {}", method, path),
+        )),
+    }
+}
+
+/// Without an `Origin` header the request does not come from a browser page
+/// (or is a same-origin GET); with one, it must name the host the request
+/// was sent to.
+fn same_origin(origin: Option<&str>, host: Option<&str>) -> bool {
+    let Some(origin) = origin else {
+        return true;
+    };
+    let origin_host = origin.split_once("://").map_or(origin, |(_, host)| host);
+    host.is_some_and(|host| host.eq_ignore_ascii_case(origin_host))
+}
+
+/// The still image kind named as in the game JSON (`box_front`, `snap`...).
+fn image_kind(name: &str) -> Option<MediaKind> {
+    serde_json::from_value(json!(name))
+        .ok()
+        .filter(|kind| MediaKind::IMAGES.contains(kind))
+}
+
+/// Decodes `%20` style escapes; invalid ones are kept as they are.
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| segment.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn write_response(mut stream: TcpStream, response: Response) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason,
+        response.content_type,
+        response.body.len()
+    )?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Sends a raw request and returns the status and body of the response.
+    fn fetch(address: SocketAddr, request: &str, mut answer: impl FnMut()) -> (u16, String) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut response = Vec::new();
+        loop {
+            assert!(Instant::now() < deadline, "no response to {}", request);
+            answer();
+            let mut chunk = [0; 4096];
+            match stream.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => response.extend_from_slice(&chunk[..n]),
+                Err(_) => continue,
+            }
+        }
+
+        let response = String::from_utf8(response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, body.to_string())
+    }
+
+    #[test]
+    fn requests_are_routed() {
+        assert_eq!(
+            route("GET", "/api/systems?x=1", Vec::new()).unwrap(),
+            Request::Systems
+        );
+        assert_eq!(
+            route(
+                "POST",
+                "/api/systems/NES/games/Contra%20(USA)/favorite",
+                Vec::new()
+            )
+            .unwrap(),
+            Request::ToggleFavorite {
+                system: "NES".into(),
+                game: "Contra (USA)".into()
+            }
+        );
+        assert_eq!(
+            route(
+                "PUT",
+                "/api/systems/NES/games/contra",
+                br#"{"developer": "Konami"}"#.to_vec()
+            )
+            .unwrap(),
+            Request::EditGame {
+                system: "NES".into(),
+                game: "contra".into(),
+                edit: Box::new(GameMetadata {
+                    developer: Some("Konami".into()),
+                    ..GameMetadata::default()
+                })
+            }
+        );
+        assert_eq!(
+            route("PUT", "/api/systems/NES/games/contra", b"{".to_vec())
+                .unwrap_err()
+                .status,
+            400
+        );
+        assert_eq!(
+            route(
+                "PUT",
+                "/api/systems/NES/games/contra",
+                br#"{"media": {"snap": "/etc/shadow"}}"#.to_vec()
+            )
+            .unwrap_err()
+            .status,
+            400
+        );
+        assert!(same_origin(None, Some("cabinet:8080")));
+        assert!(same_origin(
+            Some("http://cabinet:8080"),
+            Some("cabinet:8080")
+        ));
+        assert!(!same_origin(
+            Some("https://evil.example"),
+            Some("cabinet:8080")
+        ));
+        assert_eq!(
+            route(
+                "GET",
+                "/api/systems/NES/games/contra/media/video",
+                Vec::new()
+            )
+            .unwrap_err()
+            .status,
+            404
+        );
+        assert_eq!(
+            route("DELETE", "/api/rescan", Vec::new())
+                .unwrap_err()
+                .status,
+            404
+        );
+    }
+
+    #[test]
+    fn app_edits_the_library() {
+        use crate::app::OsirisApp;
+        use crate::config::Config;
+        use crate::ui::golden;
+        use crate::ui::renderer::Renderer;
+        use crate::ui::tui::Rotation;
+
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let config = Config {
+            state_file: dir.join(format!("osiris-web-state-{}.xml", pid)),
+            metadata_file: dir.join(format!("osiris-web-metadata-{}.xml", pid)),
+            ..Config::default()
+        };
+        let renderer = Renderer::new(golden::engine(), Rotation::None);
+        let mut app = OsirisApp::new(renderer, golden::fixture_roms(), &config);
+        let server = WebServer::bind("127.0.0.1:0", || {}).unwrap();
+        let address = server.address();
+        app.serve_web(server);
+        let mut fetch = |request: &str| fetch(address, request, || app.answer_web_requests());
+
+        let (status, page) = fetch("GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 200);
+        assert!(page.contains("OSIRIS"));
+
+        let (_, systems) = fetch("GET /api/systems HTTP/1.1\r\n\r\n");
+        let systems: Value = serde_json::from_str(&systems).unwrap();
+        assert_eq!(systems["systems"][1], json!({ "name": "NES", "games": 3 }));
+
+        let edit = r#"{"publisher": "Konami", "genres": ["Run and gun"]}"#;
+        let (status, _) = fetch(&format!(
+            "PUT /api/systems/NES/games/contra HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+            edit.len(),
+            edit
+        ));
+        assert_eq!(status, 200);
+        let (_, favorite) = fetch("POST /api/systems/NES/games/contra/favorite HTTP/1.1\r\n\r\n");
+        assert_eq!(favorite, r#"{"favorite":true}"#);
+
+        let (_, games) = fetch("GET /api/systems/NES/games HTTP/1.1\r\n\r\n");
+        let games: Value = serde_json::from_str(&games).unwrap();
+        let contra = &games[2];
+        assert_eq!(contra["publisher"], json!("Konami"));
+        assert_eq!(contra["genres"], json!(["Run and gun"]));
+        assert_eq!(contra["favorite"], json!(true));
+
+        let (status, _) = fetch("GET /api/systems/NES/games/zelda/media/snap HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 404);
+
+        let _ = std::fs::remove_file(&config.state_file);
+        std::fs::remove_file(&config.metadata_file).unwrap();
+    }
+}